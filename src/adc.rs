@@ -8,33 +8,59 @@
 //!
 //! # Examples
 //! For a simple, working example check `adc.rs` in the examples folder.
+use core::marker::PhantomData;
+use core::ptr;
+
 use cortex_m::asm;
 use embedded_hal::adc::{Channel, OneShot};
 
+use crate::dma::{self, dma1};
 use crate::rcc::{Clocks, AHB};
+use crate::time::Hertz;
 
-#[cfg(feature = "stm32f303")]
 const MAX_ADVREGEN_STARTUP_US: u32 = 10;
 
-#[cfg(feature = "stm32f303")]
 use crate::gpio::{gpioa, gpiob, gpioc, Analog};
 
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+use crate::gpio::gpiof;
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
-use crate::gpio::{gpiod, gpioe, gpiof};
+use crate::gpio::{gpiod, gpioe};
+
+use crate::stm32::{ADC1, ADC1_2};
 
-#[cfg(feature = "stm32f303")]
-use crate::stm32::{ADC1, ADC1_2, ADC2};
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+use crate::stm32::ADC2;
 
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 use crate::stm32::{ADC3, ADC3_4, ADC4};
 
@@ -47,6 +73,46 @@ pub struct Adc<ADC> {
     clocks: Clocks,
     prescale: Prescale,
     operation_mode: Option<OperationMode>,
+    overrun_mode: OverrunMode,
+    external_trigger: Option<(u8, TriggerEdge)>,
+    injected_trigger: Option<(u8, TriggerEdge)>,
+    sample_times: [SampleTime; 19],
+    resolution: Resolution,
+    align: Align,
+}
+
+/// ADC error
+#[derive(Debug)]
+pub enum Error {
+    /// A conversion result was overwritten before it was read
+    Overrun,
+    /// The source impedance is too high to charge the sampling capacitor even with
+    /// the longest sample time
+    SourceImpedance,
+    #[doc(hidden)]
+    _Extensible,
+}
+
+/// Interrupt event
+pub enum Event {
+    /// A regular conversion is complete
+    EndOfConversion,
+    /// The regular sequence is complete
+    EndOfSequence,
+    /// A regular conversion result was overwritten before it was read
+    Overrun,
+    /// An injected conversion is complete
+    InjectedEndOfConversion,
+    /// The injected sequence is complete
+    InjectedEndOfSequence,
+    /// An injected context was written, while the queue was full
+    InjectedQueueOverflow,
+    /// A result guarded by analog watchdog 1 is outside of its thresholds
+    AnalogWatchdog1,
+    /// A result guarded by analog watchdog 2 is outside of its thresholds
+    AnalogWatchdog2,
+    /// A result guarded by analog watchdog 3 is outside of its thresholds
+    AnalogWatchdog3,
 }
 
 /// ADC sampling time
@@ -55,8 +121,9 @@ pub struct Adc<ADC> {
 /// For Sampletime T_n the total conversion time (in ADC clock cycles) is
 /// 12.5 + (n + .5)
 ///
-/// TODO: there are boundaries on how this can be set depending on the hardware.
-/// Check them and implement a sample time setting mechanism.
+/// The sample time has to be long enough to charge the sampling capacitor through the
+/// impedance of the source, see `Adc::set_source_impedance`.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SampleTime {
     T_1,
     T_2,
@@ -87,14 +154,368 @@ impl SampleTime {
             SampleTime::T_601 => 0b111,
         }
     }
+
+    /// Duration in ADC clock half cycles
+    fn half_cycles(&self) -> u32 {
+        match self {
+            SampleTime::T_1 => 3,
+            SampleTime::T_2 => 5,
+            SampleTime::T_4 => 9,
+            SampleTime::T_7 => 15,
+            SampleTime::T_19 => 39,
+            SampleTime::T_61 => 123,
+            SampleTime::T_181 => 363,
+            SampleTime::T_601 => 1203,
+        }
+    }
+
+    /// Returns the shortest sample time, which is long enough for a source impedance of
+    /// `ohms`
+    ///
+    /// This follows the maximum external input impedance equation of the datasheet:
+    /// `R_AIN < t_S / (f_ADC * C_ADC * ln(2^(N + 2))) - R_ADC`, with the sampling
+    /// capacitor `C_ADC` of 5 pF and the sampling switch resistance `R_ADC` of 1 kOhm.
+    /// Returns `None` if even the longest sample time is too short.
+    pub fn for_source_impedance(
+        ohms: u32,
+        adc_clock: Hertz,
+        resolution: Resolution,
+    ) -> Option<Self> {
+        // required charging time in thousandths of ADC clock cycles, where
+        // C_ADC * ln(2) * 1000 = 5 pF * 693.1 = 6931 / 2e12 s/Ohm
+        let millicycles = u64::from(adc_clock.0 / 1000)
+            .checked_mul(u64::from(ohms) + 1000)?
+            .checked_mul(u64::from(resolution.bits() + 2))?
+            .checked_mul(6931)?
+            / 2_000_000_000;
+
+        [
+            SampleTime::T_1,
+            SampleTime::T_2,
+            SampleTime::T_4,
+            SampleTime::T_7,
+            SampleTime::T_19,
+            SampleTime::T_61,
+            SampleTime::T_181,
+            SampleTime::T_601,
+        ]
+        .iter()
+        .find(|smp| u64::from(smp.half_cycles()) * 500 >= millicycles)
+        .cloned()
+    }
+}
+
+/// ADC resolution
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    /// 12 bits
+    Bits12,
+    /// 10 bits
+    Bits10,
+    /// 8 bits
+    Bits8,
+    /// 6 bits
+    Bits6,
+}
+
+impl Resolution {
+    /// Default: 12 bits
+    pub fn default() -> Self {
+        Resolution::Bits12
+    }
+
+    /// Returns the number of bits
+    pub fn bits(&self) -> u8 {
+        match self {
+            Resolution::Bits12 => 12,
+            Resolution::Bits10 => 10,
+            Resolution::Bits8 => 8,
+            Resolution::Bits6 => 6,
+        }
+    }
+
+    /// Conversion to bits for RES in ADCx_CFGR
+    fn bitcode(&self) -> u8 {
+        match self {
+            Resolution::Bits12 => 0b00,
+            Resolution::Bits10 => 0b01,
+            Resolution::Bits8 => 0b10,
+            Resolution::Bits6 => 0b11,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
 /// ADC operation mode
-///
-/// TODO: Implement other modes (DMA, Differential,…)
 pub enum OperationMode {
     OneShot,
+    /// The regular sequence is converted once per start
+    Sequence,
+    /// The regular sequence is converted over and over again
+    Continuous,
+    /// The regular sequence is converted continuously into a circular DMA buffer
+    DmaCircular,
+}
+
+/// Operation of the two ADCs of a pair (ADC1 and ADC2, or ADC3 and ADC4)
+///
+/// In all modes, the master (ADC1 or ADC3) is started and triggered, and the slave follows.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DualMode {
+    /// Both regular sequences are converted at the same time
+    RegularSimultaneous,
+    /// Both injected sequences are converted at the same time
+    InjectedSimultaneous,
+    /// Both regular and both injected sequences are converted at the same time
+    RegularInjectedSimultaneous,
+    /// Both regular sequences are converted at the same time, the injected sequences
+    /// alternately on each injected trigger
+    RegularSimultaneousAlternateTrigger,
+    /// The regular sequences are converted alternately, the slave starts `delay` ADC clock
+    /// cycles (1 to 16) after the master
+    Interleaved { delay: u8 },
+    /// Interleaved regular and simultaneous injected conversions
+    InterleavedInjectedSimultaneous { delay: u8 },
+    /// The injected sequences are converted alternately on each injected trigger
+    AlternateTrigger,
+}
+
+impl DualMode {
+    /// Conversion to bits for DUAL in ADCx_CCR
+    fn bitcode(&self) -> u8 {
+        match self {
+            DualMode::RegularInjectedSimultaneous => 0b00001,
+            DualMode::RegularSimultaneousAlternateTrigger => 0b00010,
+            DualMode::InterleavedInjectedSimultaneous { .. } => 0b00011,
+            DualMode::InjectedSimultaneous => 0b00101,
+            DualMode::RegularSimultaneous => 0b00110,
+            DualMode::Interleaved { .. } => 0b00111,
+            DualMode::AlternateTrigger => 0b01001,
+        }
+    }
+
+    /// Delay between the sampling phases of interleaved conversions in ADC clock cycles
+    fn delay(&self) -> u8 {
+        match self {
+            DualMode::Interleaved { delay }
+            | DualMode::InterleavedInjectedSimultaneous { delay } => {
+                assert!(*delay >= 1 && *delay <= 16, "invalid interleaved delay");
+                *delay
+            }
+            _ => 1,
+        }
+    }
+}
+
+/// Two ADCs, which convert together in a `DualMode`
+///
+/// The sequences, triggers and the other settings are configured on the single ADCs with
+/// `master_mut` and `slave_mut`. The results of both are packed into one word of the
+/// common data register, the master's in the lower and the slave's in the upper half.
+pub struct DualAdc<MASTER, SLAVE, COMMON> {
+    master: Adc<MASTER>,
+    slave: Adc<SLAVE>,
+    common: COMMON,
+}
+
+impl<MASTER, SLAVE, COMMON> DualAdc<MASTER, SLAVE, COMMON> {
+    /// Returns the master ADC
+    pub fn master(&self) -> &Adc<MASTER> {
+        &self.master
+    }
+
+    /// Returns the master ADC
+    pub fn master_mut(&mut self) -> &mut Adc<MASTER> {
+        &mut self.master
+    }
+
+    /// Returns the slave ADC
+    pub fn slave(&self) -> &Adc<SLAVE> {
+        &self.slave
+    }
+
+    /// Returns the slave ADC
+    pub fn slave_mut(&mut self) -> &mut Adc<SLAVE> {
+        &mut self.slave
+    }
+
+    /// Splits a word of the common data register into the master's and the slave's result
+    pub fn split(word: u32) -> (u16, u16) {
+        (word as u16, (word >> 16) as u16)
+    }
+}
+
+/// Handling of a regular conversion result, which was not read before the next one is
+/// complete
+#[derive(Clone, Copy, PartialEq)]
+pub enum OverrunMode {
+    /// The old result is kept and the new one is lost. With DMA, the requests stop until
+    /// the overrun flag is cleared.
+    Preserve,
+    /// The old result is overwritten
+    Overwrite,
+}
+
+impl OverrunMode {
+    /// Default: the old result is kept
+    pub fn default() -> Self {
+        OverrunMode::Preserve
+    }
+}
+
+/// Regular sequence of up to 16 conversions
+///
+/// A channel may appear several times. As the sample time is configured per channel,
+/// the one added last applies to all of its conversions.
+pub struct Sequence<ADC> {
+    channels: [(u8, SampleTime); 16],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Sequence<ADC> {
+    /// Creates an empty sequence
+    pub fn new() -> Self {
+        Sequence {
+            channels: [(0, SampleTime::default()); 16],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a conversion of `pin`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already holds 16 conversions.
+    pub fn add<PIN>(mut self, _pin: &PIN, sample_time: SampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 16, "ADC sequence is full");
+        self.channels[self.len] = (PIN::channel(), sample_time);
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of conversions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence holds no conversions
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn conversions(&self) -> &[(u8, SampleTime)] {
+        &self.channels[..self.len]
+    }
+}
+
+impl<ADC> Default for Sequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Injected sequence of up to 4 conversions
+///
+/// The results end up in the injected data registers, one per rank. The conversions use
+/// the sample times programmed for their channels, see `Adc::set_sample_time`: an
+/// injected sequence may interrupt running regular conversions, while the sample times
+/// are not writable.
+pub struct InjectedSequence<ADC> {
+    channels: [u8; 4],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> InjectedSequence<ADC> {
+    /// Creates an empty sequence
+    pub fn new() -> Self {
+        InjectedSequence {
+            channels: [0; 4],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a conversion of `pin`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already holds 4 conversions.
+    pub fn add<PIN>(mut self, _pin: &PIN) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 4, "ADC injected sequence is full");
+        self.channels[self.len] = PIN::channel();
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of conversions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence holds no conversions
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn conversions(&self) -> &[u8] {
+        &self.channels[..self.len]
+    }
+}
+
+impl<ADC> Default for InjectedSequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Set of channels guarded by analog watchdog 2 or 3
+pub struct WatchdogChannels<ADC> {
+    mask: u32,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> WatchdogChannels<ADC> {
+    /// Creates an empty set
+    pub fn new() -> Self {
+        WatchdogChannels {
+            mask: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Adds the channel of `pin`
+    pub fn add<PIN>(mut self, _pin: &PIN) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        self.mask |= 1 << PIN::channel();
+        self
+    }
+}
+
+impl<ADC> Default for WatchdogChannels<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handling of an empty injected context queue
+#[derive(Clone, Copy, PartialEq)]
+pub enum InjectedQueueMode {
+    /// The last context stays active, once all queued contexts were consumed
+    KeepLast,
+    /// The queue runs empty, which disables the injected conversions until the next
+    /// context is written
+    AllowEmpty,
 }
 
 #[derive(Clone, Copy)]
@@ -122,6 +543,7 @@ impl Prescale {
 }
 
 /// ADC data register alignment
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Align {
     /// Right alignment of output data
     Right,
@@ -146,6 +568,323 @@ impl Align {
     }
 }
 
+/// Conversion result, which knows its resolution and alignment
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    raw: u16,
+    resolution: Resolution,
+    align: Align,
+}
+
+impl Reading {
+    /// Returns the content of the data register
+    pub fn raw(&self) -> u16 {
+        self.raw
+    }
+
+    /// Returns the right aligned value
+    pub fn value(&self) -> u16 {
+        let bits = u16::from(self.resolution.bits());
+        match self.align {
+            Align::Right => self.raw,
+            // 6-bit results are left aligned to a byte
+            Align::Left if bits == 6 => self.raw >> 2,
+            Align::Left => self.raw >> (16 - bits),
+        }
+    }
+
+    /// Returns the value, which corresponds to the reference voltage
+    pub fn full_scale(&self) -> u16 {
+        (1 << self.resolution.bits()) - 1
+    }
+
+    /// Converts the value to millivolts, given the reference voltage VDDA in millivolts
+    pub fn millivolts(&self, vref_mv: u32) -> u32 {
+        u32::from(self.value()) * vref_mv / u32::from(self.full_scale())
+    }
+
+    /// Returns the signed value of a differential conversion
+    ///
+    /// The middle of the range corresponds to equal voltages on both inputs.
+    pub fn signed_value(&self) -> i16 {
+        self.value() as i16 - (1 << (self.resolution.bits() - 1))
+    }
+
+    /// Converts the value of a differential conversion to the difference of the input
+    /// voltages in millivolts, given the reference voltage VDDA in millivolts
+    pub fn differential_millivolts(&self, vref_mv: u32) -> i32 {
+        i32::from(self.signed_value()) * vref_mv as i32 / (1 << (self.resolution.bits() - 1))
+    }
+}
+
+/// Differential input, which converts the voltage of the pin `P` relative to the pin `N`
+///
+/// It is converted on the channel of `P`, once it is enabled with `Adc::set_differential`.
+pub struct Differential<P, N> {
+    positive: P,
+    negative: N,
+}
+
+impl<P, N> Differential<P, N> {
+    /// Pairs a positive and a negative input of `ADC`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel of `negative` does not follow the channel of `positive`.
+    pub fn new<ADC>(positive: P, negative: N) -> Self
+    where
+        P: Channel<ADC, ID = u8>,
+        N: Channel<ADC, ID = u8>,
+    {
+        assert!(
+            N::channel() == P::channel() + 1,
+            "the negative input has to be the next channel"
+        );
+
+        Differential { positive, negative }
+    }
+
+    /// Releases the pins
+    pub fn free(self) -> (P, N) {
+        (self.positive, self.negative)
+    }
+}
+
+impl<ADC, P, N> Channel<ADC> for Differential<P, N>
+where
+    P: Channel<ADC, ID = u8>,
+    N: Channel<ADC, ID = u8>,
+{
+    type ID = u8;
+
+    fn channel() -> u8 {
+        P::channel()
+    }
+}
+
+/// Edge of an external trigger, which starts a regular sequence
+#[derive(Clone, Copy, PartialEq)]
+pub enum TriggerEdge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both edges
+    Both,
+}
+
+impl TriggerEdge {
+    /// Conversion to bits for EXTEN in ADCx_CFGR
+    fn bitcode(&self) -> u8 {
+        match self {
+            TriggerEdge::Rising => 0b01,
+            TriggerEdge::Falling => 0b10,
+            TriggerEdge::Both => 0b11,
+        }
+    }
+}
+
+/// Returns CONT, EXTSEL and EXTEN of ADCx_CFGR for a regular sequence
+///
+/// With an external trigger, each trigger event converts the sequence once. CONT
+/// would go on converting after the first event, so it is only set for continuous
+/// sequences started by software.
+fn regular_cfgr(external_trigger: Option<(u8, TriggerEdge)>, continuous: bool) -> (bool, u8, u8) {
+    match external_trigger {
+        Some((extsel, edge)) => (false, extsel, edge.bitcode()),
+        None => (continuous, 0, 0b00),
+    }
+}
+
+/// External trigger of the regular sequence of ADC1 and ADC2
+///
+/// The timer events are configured as master mode or output compare of the timer, e.g.
+/// with the `pwm` module for a trigger in the middle of the PWM period. Only the
+/// triggers of the timers of the selected device are available, e.g. the HRTIM ADC
+/// triggers take the places of the TIM8 events on the `stm32f334`.
+#[derive(Clone, Copy)]
+pub enum Adc12Trigger {
+    Tim1Cc1 = 0,
+    Tim1Cc2 = 1,
+    Tim1Cc3 = 2,
+    Tim2Cc2 = 3,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Trgo = 4,
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Cc4 = 5,
+    Exti11 = 6,
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo = 7,
+    #[cfg(any(feature = "stm32f334", feature = "stm32f328"))]
+    HrtimAdcTrg1 = 7,
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo2 = 8,
+    #[cfg(any(feature = "stm32f334", feature = "stm32f328"))]
+    HrtimAdcTrg3 = 8,
+    Tim1Trgo = 9,
+    Tim1Trgo2 = 10,
+    Tim2Trgo = 11,
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Trgo = 12,
+    Tim6Trgo = 13,
+    Tim15Trgo = 14,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Cc4 = 15,
+}
+
+/// External trigger of the regular sequence of ADC3 and ADC4
+///
+/// See `Adc12Trigger`.
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+#[derive(Clone, Copy)]
+pub enum Adc34Trigger {
+    Tim3Cc1 = 0,
+    Tim2Cc3 = 1,
+    Tim1Cc3 = 2,
+    Tim8Cc1 = 3,
+    Tim8Trgo = 4,
+    Exti2 = 5,
+    Tim4Cc1 = 6,
+    Tim2Trgo = 7,
+    Tim8Trgo2 = 8,
+    Tim1Trgo = 9,
+    Tim1Trgo2 = 10,
+    Tim3Trgo = 11,
+    Tim4Trgo = 12,
+    Tim7Trgo = 13,
+    Tim15Trgo = 14,
+    Tim2Cc1 = 15,
+}
+
+/// External trigger of the injected sequence of ADC1 and ADC2
+///
+/// See `Adc12Trigger`.
+#[derive(Clone, Copy)]
+pub enum Adc12InjectedTrigger {
+    Tim1Trgo = 0,
+    Tim1Cc4 = 1,
+    Tim2Trgo = 2,
+    Tim2Cc1 = 3,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Cc4 = 4,
+    #[cfg(any(
+        feature = "stm32f302",
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim4Trgo = 5,
+    Exti15 = 6,
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Cc4 = 7,
+    Tim1Trgo2 = 8,
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo = 9,
+    #[cfg(any(feature = "stm32f334", feature = "stm32f328"))]
+    HrtimAdcTrg2 = 9,
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398",
+    ))]
+    Tim8Trgo2 = 10,
+    #[cfg(any(feature = "stm32f334", feature = "stm32f328"))]
+    HrtimAdcTrg4 = 10,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Cc3 = 11,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Trgo = 12,
+    #[cfg(not(any(feature = "stm32f301", feature = "stm32f318")))]
+    Tim3Cc1 = 13,
+    Tim6Trgo = 14,
+    Tim15Trgo = 15,
+}
+
+/// External trigger of the injected sequence of ADC3 and ADC4
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+#[derive(Clone, Copy)]
+pub enum Adc34InjectedTrigger {
+    Tim1Trgo = 0,
+    Tim1Cc4 = 1,
+    Tim4Cc3 = 2,
+    Tim8Cc2 = 3,
+    Tim8Cc4 = 4,
+    Tim4Cc4 = 6,
+    Tim4Trgo = 7,
+    Tim1Trgo2 = 8,
+    Tim8Trgo = 9,
+    Tim8Trgo2 = 10,
+    Tim1Cc3 = 11,
+    Tim3Trgo = 12,
+    Tim2Trgo = 13,
+    Tim7Trgo = 14,
+    Tim15Trgo = 15,
+}
+
 /// Maps pins to ADC Channels.
 macro_rules! adc_pins {
     ($ADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
@@ -160,9 +899,7 @@ macro_rules! adc_pins {
 }
 
 // # ADC1 Pin/Channel mapping
-// ## f303
 
-#[cfg(feature = "stm32f303")]
 adc_pins!(ADC1,
     gpioa::PA0<Analog> => 1_u8,
     gpioa::PA1<Analog> => 2_u8,
@@ -174,7 +911,14 @@ adc_pins!(ADC1,
     gpioc::PC3<Analog> => 9_u8,
 );
 
-#[cfg(any(feature = "stm32f303x6", feature = "stm32f303x8"))]
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328",
+))]
 adc_pins!(ADC1,
     gpiob::PB0<Analog> => 11_u8,
     gpiob::PB1<Analog> => 12_u8,
@@ -182,10 +926,13 @@ adc_pins!(ADC1,
 );
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC1,
     gpiof::PF4<Analog> => 5_u8,
@@ -193,9 +940,15 @@ adc_pins!(ADC1,
 );
 
 // # ADC2 Pin/Channel mapping
-// ## f303
 
-#[cfg(feature = "stm32f303")]
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
 adc_pins!(ADC2,
     gpioa::PA4<Analog> => 1_u8,
     gpioa::PA5<Analog> => 2_u8,
@@ -210,7 +963,12 @@ adc_pins!(ADC2,
     gpioc::PC3<Analog> => 9_u8,
 );
 
-#[cfg(any(feature = "stm32f303x6", feature = "stm32f303x8"))]
+#[cfg(any(
+    feature = "stm32f303x6",
+    feature = "stm32f303x8",
+    feature = "stm32f334",
+    feature = "stm32f328",
+))]
 adc_pins!(ADC2,
     gpiob::PB12<Analog> => 13_u8,
     gpiob::PB14<Analog> => 14_u8,
@@ -218,23 +976,27 @@ adc_pins!(ADC2,
 );
 
 #[cfg(any(
+    feature = "stm32f302",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC2,
     gpiof::PF2<Analog> => 10_u8,
 );
 
 // # ADC3 Pin/Channel mapping
-// ## f303
 
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC3,
     gpiob::PB1<Analog> => 1_u8,
@@ -256,13 +1018,14 @@ adc_pins!(ADC3,
 );
 
 // # ADC4 Pin/Channel mapping
-// ## f303
 
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc_pins!(ADC4,
     gpioe::PE14<Analog> => 1_u8,
@@ -281,13 +1044,155 @@ adc_pins!(ADC4,
     gpiod::PD14<Analog> => 11_u8,
 );
 
+// # Internal channels
+
+/// Temperature sensor calibration value at 30 °C and VDDA = 3.3 V
+const TS_CAL1: *const u16 = 0x1FFF_F7B8 as *const u16;
+/// Temperature sensor calibration value at 110 °C and VDDA = 3.3 V
+const TS_CAL2: *const u16 = 0x1FFF_F7C2 as *const u16;
+/// Internal reference voltage calibration value at 30 °C and VDDA = 3.3 V
+const VREFINT_CAL: *const u16 = 0x1FFF_F7BA as *const u16;
+/// VDDA during the factory calibration in millivolts
+const VDDA_CAL_MV: u32 = 3300;
+
+/// Scales a result to 12 bits, the resolution of the factory calibration values
+fn to_12_bits(reading: Reading) -> u32 {
+    u32::from(reading.value()) << (12 - reading.resolution.bits())
+}
+
+/// Internal temperature sensor (ADC1_IN16)
+///
+/// The sensor needs a sample time of at least 2.2 µs.
+pub struct TemperatureSensor {
+    _0: (),
+}
+
+impl TemperatureSensor {
+    /// Enables the temperature sensor
+    pub fn new(adc_common: &mut ADC1_2) -> Self {
+        adc_common.ccr.modify(|_, w| w.tsen().set_bit());
+        TemperatureSensor { _0: () }
+    }
+
+    /// Disables the temperature sensor
+    pub fn disable(self, adc_common: &mut ADC1_2) {
+        adc_common.ccr.modify(|_, w| w.tsen().clear_bit());
+    }
+
+    /// Converts a result to degrees Celsius using the factory calibration, given the
+    /// actual VDDA in millivolts (see `VRefInt::vdda_millivolts`)
+    pub fn celsius(reading: Reading, vdda_mv: u32) -> f32 {
+        // NOTE(unsafe) read only calibration values in the system memory
+        let (cal1, cal2) = unsafe { (ptr::read_volatile(TS_CAL1), ptr::read_volatile(TS_CAL2)) };
+        // the result, as if it was converted with VDDA during the calibration
+        let value = (to_12_bits(reading) * vdda_mv) as f32 / VDDA_CAL_MV as f32;
+
+        (110.0 - 30.0) * (value - f32::from(cal1)) / (f32::from(cal2) - f32::from(cal1)) + 30.0
+    }
+}
+
+/// Half of the backup supply voltage VBAT (ADC1_IN17)
+pub struct VBat {
+    _0: (),
+}
+
+impl VBat {
+    /// Enables the VBAT/2 bridge
+    ///
+    /// It drains the backup battery, so it should only be enabled for a measurement.
+    pub fn new(adc_common: &mut ADC1_2) -> Self {
+        adc_common.ccr.modify(|_, w| w.vbaten().set_bit());
+        VBat { _0: () }
+    }
+
+    /// Disables the VBAT/2 bridge
+    pub fn disable(self, adc_common: &mut ADC1_2) {
+        adc_common.ccr.modify(|_, w| w.vbaten().clear_bit());
+    }
+
+    /// Converts a result to VBAT in millivolts, given VDDA in millivolts
+    pub fn millivolts(reading: Reading, vdda_mv: u32) -> u32 {
+        2 * reading.millivolts(vdda_mv)
+    }
+}
+
+/// Internal reference voltage VREFINT (ADCx_IN18), shared by the ADCs of `COMMON`
+pub struct VRefInt<COMMON> {
+    _common: PhantomData<COMMON>,
+}
+
+impl<COMMON> VRefInt<COMMON> {
+    /// Computes VDDA in millivolts from a result using the factory calibration
+    ///
+    /// Returns `None` for a zero result, which no valid VDDA produces.
+    pub fn vdda_millivolts(reading: Reading) -> Option<u32> {
+        // NOTE(unsafe) read only calibration value in the system memory
+        let cal = u32::from(unsafe { ptr::read_volatile(VREFINT_CAL) });
+        match to_12_bits(reading) {
+            0 => None,
+            value => Some(VDDA_CAL_MV * cal / value),
+        }
+    }
+}
+
+/// Implements the reference voltage of the ADCs of a common block
+macro_rules! vrefint {
+    ($ADC_COMMON:ident: ($($ADC:ident),+)) => {
+        impl VRefInt<$ADC_COMMON> {
+            /// Enables the internal reference voltage
+            pub fn new(adc_common: &mut $ADC_COMMON) -> Self {
+                adc_common.ccr.modify(|_, w| w.vrefen().set_bit());
+                VRefInt { _common: PhantomData }
+            }
+
+            /// Disables the internal reference voltage
+            pub fn disable(self, adc_common: &mut $ADC_COMMON) {
+                adc_common.ccr.modify(|_, w| w.vrefen().clear_bit());
+            }
+        }
+
+        $(
+            adc_pins!($ADC,
+                VRefInt<$ADC_COMMON> => 18_u8,
+            );
+        )+
+    };
+}
+
+adc_pins!(ADC1,
+    TemperatureSensor => 16_u8,
+    VBat => 17_u8,
+);
+
+#[cfg(any(feature = "stm32f301", feature = "stm32f318"))]
+vrefint!(ADC1_2: (ADC1));
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+vrefint!(ADC1_2: (ADC1, ADC2));
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+vrefint!(ADC3_4: (ADC3, ADC4));
+
 /// Abstract implementation of ADC functionality
 ///
 /// Do not use directly. See adc12_hal for a applicable Macro.
-/// TODO: Extend/generalize beyond f303
 macro_rules! adc_hal {
     ($(
-            $ADC:ident: ($adcx:ident, $ADC_COMMON:ident),
+            $ADC:ident: ($adcx:ident, $ADC_COMMON:ident, $Trigger:ident, $InjectedTrigger:ident),
     )+) => {
         $(
             impl Adc<$ADC> {
@@ -306,10 +1211,16 @@ macro_rules! adc_hal {
                         clocks,
                         prescale : Prescale::default(),
                         operation_mode: None,
+                        overrun_mode: OverrunMode::default(),
+                        external_trigger: None,
+                        injected_trigger: None,
+                        sample_times: [SampleTime::default(); 19],
+                        resolution: Resolution::default(),
+                        align: Align::default(),
                     };
                     this_adc.enable_clock(ahb, adc_common);
                     this_adc.set_align(Align::default());
-                    this_adc.calibrate();
+                    this_adc.calibrate(false);
                     // ADEN bit cannot be set during ADCAL=1
                     // and 4 ADC clock cycle after the ADCAL
                     // bit is cleared by hardware
@@ -321,21 +1232,589 @@ macro_rules! adc_hal {
                 /// sets up adc in one shot mode for a single channel
                 pub fn setup_oneshot(&mut self) {
                     // stop and clear overrun events
-                    self.rb.cr.modify(|_, w| w.adstp().set_bit());
-                    self.rb.isr.modify(|_, w| w.ovr().clear_bit());
+                    self.stop();
+                    self.clear_event(Event::Overrun);
 
-                    self.rb.cfgr.modify(|_, w| w
+                    // NOTE(unsafe): EXTEN = 0 selects the software trigger
+                    self.rb.cfgr.modify(|_, w| unsafe { w
                         .cont().clear_bit()
                         .ovrmod().clear_bit()
-                    );
+                        .exten().bits(0b00)
+                    });
 
                     self.rb.sqr1.modify(|_, w| w.l().bits(0));
 
                     self.operation_mode = Some(OperationMode::OneShot);
                 }
 
-                fn set_align(&self, align: Align) {
+                /// Programs the regular sequence
+                ///
+                /// Stops ongoing regular and injected conversions, as the sample times of
+                /// the sequence are only writable while neither is ongoing. The sequence is
+                /// converted once per start.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `sequence` is empty.
+                pub fn set_sequence(&mut self, sequence: &Sequence<$ADC>) {
+                    self.program_sequence(sequence, false);
+                }
+
+                /// Programs the regular sequence, which is converted over and over again
+                /// if `continuous` and no external trigger is set
+                fn program_sequence(&mut self, sequence: &Sequence<$ADC>, continuous: bool) {
+                    assert!(!sequence.is_empty(), "empty ADC sequence");
+
+                    self.stop();
+                    self.stop_injected();
+                    let overwrite = self.overrun_mode == OverrunMode::Overwrite;
+                    let (cont, extsel, exten) = regular_cfgr(self.external_trigger, continuous);
+                    // NOTE(unsafe): EXTSEL and EXTEN are taken from the typed trigger
+                    self.rb.cfgr.modify(|_, w| unsafe { w
+                        .cont().bit(cont)
+                        .ovrmod().bit(overwrite)
+                        .extsel().bits(extsel)
+                        .exten().bits(exten)
+                    });
+
+                    // L: sequence length - 1, followed by SQ1 in SQR1,
+                    // SQR2 to SQR4 hold 5 conversions each, in 6-bit fields
+                    let mut sqr = [(sequence.len() - 1) as u32, 0, 0, 0];
+                    for (i, &(chan, smp)) in sequence.conversions().iter().enumerate() {
+                        let (reg, field) = ((i + 1) / 5, (i + 1) % 5);
+                        sqr[reg] |= u32::from(chan) << (6 * field);
+                        self.set_chan_smps(chan, smp);
+                    }
+
+                    // NOTE(unsafe): the channels are valid, see `Sequence::add`
+                    unsafe {
+                        self.rb.sqr1.write(|w| w.bits(sqr[0]));
+                        self.rb.sqr2.write(|w| w.bits(sqr[1]));
+                        self.rb.sqr3.write(|w| w.bits(sqr[2]));
+                        self.rb.sqr4.write(|w| w.bits(sqr[3]));
+                    }
+
+                    self.operation_mode = Some(OperationMode::Sequence);
+                }
+
+                /// Converts `sequence` once and waits for the results
+                ///
+                /// `results` receives one value per conversion of the sequence.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `results` is shorter than the sequence.
+                pub fn convert_sequence(
+                    &mut self,
+                    sequence: &Sequence<$ADC>,
+                    results: &mut [u16],
+                ) -> Result<(), Error> {
+                    assert!(results.len() >= sequence.len());
+
+                    self.start_sequence(sequence);
+                    for result in results[..sequence.len()].iter_mut() {
+                        loop {
+                            let isr = self.rb.isr.read();
+                            if isr.ovr().bit_is_set() {
+                                self.stop();
+                                self.clear_event(Event::Overrun);
+                                return Err(Error::Overrun);
+                            } else if isr.eoc().bit_is_set() {
+                                break;
+                            }
+                        }
+                        *result = self.read_data();
+                    }
+                    self.clear_event(Event::EndOfSequence);
+
+                    Ok(())
+                }
+
+                /// Programs `sequence` and starts converting it
+                ///
+                /// The results are taken with `read_data`, e.g. when the `EndOfConversion`
+                /// interrupt fires.
+                pub fn start_sequence(&mut self, sequence: &Sequence<$ADC>) {
+                    self.set_sequence(sequence);
+                    self.clear_event(Event::EndOfConversion);
+                    self.clear_event(Event::EndOfSequence);
+                    self.clear_event(Event::Overrun);
+                    self.rb.cr.modify(|_, w| w.adstart().set_bit());
+                }
+
+                /// Converts `sequence`, while the DMA moves the results into `buffer`
+                ///
+                /// The transfer is complete once `buffer` is full, so it should be as long
+                /// as the sequence.
+                pub fn read_dma<C>(
+                    mut self,
+                    sequence: &Sequence<$ADC>,
+                    buffer: &'static mut [u16],
+                    mut channel: C,
+                ) -> dma::Transfer<&'static mut [u16], C, Self>
+                where
+                    C: dma::RxChannel<$ADC>,
+                {
+                    self.set_sequence(sequence);
+                    // DMACFG: one shot mode, the requests stop once the transfer is complete
+                    self.rb.cfgr.modify(|_, w| w.dmacfg().clear_bit());
+                    self.set_dma_address(&mut channel);
+
+                    dma::Transfer::start_read(buffer, channel, self)
+                }
+
+                /// Starts the sequences started from now on by `trigger` instead of software
+                ///
+                /// Starting a sequence arms the ADC. Then each trigger event converts the
+                /// whole sequence, until the conversions are stopped. With DMA, the buffer
+                /// may hold the results of several sequences.
+                pub fn set_external_trigger(&mut self, trigger: $Trigger, edge: TriggerEdge) {
+                    self.external_trigger = Some((trigger as u8, edge));
+                }
+
+                /// Starts the sequences started from now on by software again
+                pub fn disable_external_trigger(&mut self) {
+                    self.external_trigger = None;
+                }
+
+                /// Sets the handling of overruns for the sequences started from now on
+                pub fn set_overrun_mode(&mut self, mode: OverrunMode) {
+                    self.overrun_mode = mode;
+                }
+
+                /// Programs `sequence` and converts it continuously
+                ///
+                /// With an external trigger, each trigger event converts the sequence once.
+                /// The results are taken with `read_data`, until the conversions are
+                /// stopped with `stop`.
+                pub fn start_continuous(&mut self, sequence: &Sequence<$ADC>) {
+                    self.program_sequence(sequence, true);
+                    self.operation_mode = Some(OperationMode::Continuous);
+
+                    self.clear_event(Event::EndOfConversion);
+                    self.clear_event(Event::EndOfSequence);
+                    self.clear_event(Event::Overrun);
+                    self.rb.cr.modify(|_, w| w.adstart().set_bit());
+                }
+
+                /// Converts `sequence` continuously, while the DMA fills both halves of
+                /// `buffer` in turn
+                ///
+                /// Each half is handed out by `CircBuffer::peek`, once the DMA completed it.
+                /// The half and full transfer interrupts are enabled on the channel with
+                /// `CircBuffer::channel_mut`. The halves should hold a multiple of the
+                /// sequence length, so every half starts with the first conversion. With an
+                /// external trigger, each trigger event converts the sequence once.
+                ///
+                /// With `OverrunMode::Preserve`, an overrun stops the DMA requests and
+                /// `peek` returns `dma::Error::Target(Error::Overrun)`. `CircBuffer::restart`
+                /// clears the overrun flag and restarts the conversions and the DMA at the
+                /// beginning of the buffer. Other events of the ADC are accessible through
+                /// `CircBuffer::target_mut`.
+                pub fn read_dma_circular<C>(
+                    mut self,
+                    sequence: &Sequence<$ADC>,
+                    buffer: &'static mut [u16],
+                    mut channel: C,
+                ) -> dma::CircBuffer<u16, C, Self>
+                where
+                    C: dma::RxChannel<$ADC>,
+                {
+                    self.program_sequence(sequence, true);
+                    // DMACFG: circular mode, the requests go on after the last transfer
+                    self.rb.cfgr.modify(|_, w| w.dmacfg().set_bit());
+                    self.operation_mode = Some(OperationMode::DmaCircular);
+                    self.set_dma_address(&mut channel);
+
+                    dma::CircBuffer::start_read(buffer, channel, self)
+                }
+
+                /// Checks for an overrun and clears it
+                ///
+                /// If the old results are preserved, the conversions go on after the flag is
+                /// cleared, but the DMA requests have to be restarted, see
+                /// `read_dma_circular`.
+                pub fn check_overrun(&mut self) -> Result<(), Error> {
+                    if self.event_occurred(Event::Overrun) {
+                        self.clear_event(Event::Overrun);
+                        Err(Error::Overrun)
+                    } else {
+                        Ok(())
+                    }
+                }
+
+                fn set_dma_address<C: dma::Channel>(&self, channel: &mut C) {
+                    // NOTE(unsafe) DR holds a 16-bit result
+                    unsafe {
+                        channel.set_peripheral_address(
+                            &self.rb.dr as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+                }
+
+                /// Returns the result of the last regular conversion
+                pub fn read_data(&mut self) -> u16 {
+                    self.rb.dr.read().rdata().bits()
+                }
+
+                /// Starts listening for an interrupt event
+                pub fn listen(&mut self, event: Event) {
+                    match event {
+                        Event::EndOfConversion => self.rb.ier.modify(|_, w| w.eocie().set_bit()),
+                        Event::EndOfSequence => self.rb.ier.modify(|_, w| w.eosie().set_bit()),
+                        Event::Overrun => self.rb.ier.modify(|_, w| w.ovrie().set_bit()),
+                        Event::InjectedEndOfConversion => self.rb.ier.modify(|_, w| w.jeocie().set_bit()),
+                        Event::InjectedEndOfSequence => self.rb.ier.modify(|_, w| w.jeosie().set_bit()),
+                        Event::InjectedQueueOverflow => self.rb.ier.modify(|_, w| w.jqovfie().set_bit()),
+                        Event::AnalogWatchdog1 => self.rb.ier.modify(|_, w| w.awd1ie().set_bit()),
+                        Event::AnalogWatchdog2 => self.rb.ier.modify(|_, w| w.awd2ie().set_bit()),
+                        Event::AnalogWatchdog3 => self.rb.ier.modify(|_, w| w.awd3ie().set_bit()),
+                    }
+                }
+
+                /// Stops listening for an interrupt event
+                pub fn unlisten(&mut self, event: Event) {
+                    match event {
+                        Event::EndOfConversion => self.rb.ier.modify(|_, w| w.eocie().clear_bit()),
+                        Event::EndOfSequence => self.rb.ier.modify(|_, w| w.eosie().clear_bit()),
+                        Event::Overrun => self.rb.ier.modify(|_, w| w.ovrie().clear_bit()),
+                        Event::InjectedEndOfConversion => self.rb.ier.modify(|_, w| w.jeocie().clear_bit()),
+                        Event::InjectedEndOfSequence => self.rb.ier.modify(|_, w| w.jeosie().clear_bit()),
+                        Event::InjectedQueueOverflow => self.rb.ier.modify(|_, w| w.jqovfie().clear_bit()),
+                        Event::AnalogWatchdog1 => self.rb.ier.modify(|_, w| w.awd1ie().clear_bit()),
+                        Event::AnalogWatchdog2 => self.rb.ier.modify(|_, w| w.awd2ie().clear_bit()),
+                        Event::AnalogWatchdog3 => self.rb.ier.modify(|_, w| w.awd3ie().clear_bit()),
+                    }
+                }
+
+                /// Returns `true` if the flag of `event` is set
+                pub fn event_occurred(&self, event: Event) -> bool {
+                    let isr = self.rb.isr.read();
+                    match event {
+                        Event::EndOfConversion => isr.eoc().bit_is_set(),
+                        Event::EndOfSequence => isr.eos().bit_is_set(),
+                        Event::Overrun => isr.ovr().bit_is_set(),
+                        Event::InjectedEndOfConversion => isr.jeoc().bit_is_set(),
+                        Event::InjectedEndOfSequence => isr.jeos().bit_is_set(),
+                        Event::InjectedQueueOverflow => isr.jqovf().bit_is_set(),
+                        Event::AnalogWatchdog1 => isr.awd1().bit_is_set(),
+                        Event::AnalogWatchdog2 => isr.awd2().bit_is_set(),
+                        Event::AnalogWatchdog3 => isr.awd3().bit_is_set(),
+                    }
+                }
+
+                /// Clears the flag of `event`
+                ///
+                /// `EndOfConversion` is cleared by `read_data` as well.
+                pub fn clear_event(&mut self, event: Event) {
+                    // the flags are cleared by writing 1, writing 0 has no effect
+                    match event {
+                        Event::EndOfConversion => self.rb.isr.write(|w| w.eoc().set_bit()),
+                        Event::EndOfSequence => self.rb.isr.write(|w| w.eos().set_bit()),
+                        Event::Overrun => self.rb.isr.write(|w| w.ovr().set_bit()),
+                        Event::InjectedEndOfConversion => self.rb.isr.write(|w| w.jeoc().set_bit()),
+                        Event::InjectedEndOfSequence => self.rb.isr.write(|w| w.jeos().set_bit()),
+                        Event::InjectedQueueOverflow => self.rb.isr.write(|w| w.jqovf().set_bit()),
+                        Event::AnalogWatchdog1 => self.rb.isr.write(|w| w.awd1().set_bit()),
+                        Event::AnalogWatchdog2 => self.rb.isr.write(|w| w.awd2().set_bit()),
+                        Event::AnalogWatchdog3 => self.rb.isr.write(|w| w.awd3().set_bit()),
+                    }
+                }
+
+                /// Stops ongoing regular conversions
+                pub fn stop(&mut self) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.rb.cr.modify(|_, w| w.adstp().set_bit());
+                        while self.rb.cr.read().adstp().bit_is_set() {}
+                    }
+                }
+
+                /// Starts the injected sequences started from now on by `trigger` instead of
+                /// software
+                ///
+                /// A triggered injected sequence interrupts an ongoing regular conversion,
+                /// which is restarted afterwards.
+                pub fn set_injected_trigger(
+                    &mut self,
+                    trigger: $InjectedTrigger,
+                    edge: TriggerEdge,
+                ) {
+                    self.injected_trigger = Some((trigger as u8, edge));
+                }
+
+                /// Starts the injected sequences started from now on by software again
+                pub fn disable_injected_trigger(&mut self) {
+                    self.injected_trigger = None;
+                }
+
+                /// Writes an injected context of `sequence` and the injected trigger, and
+                /// arms the injected conversions
+                ///
+                /// While injected conversions are armed, the context is queued. The queue
+                /// holds up to 2 contexts, which become active one after another, each
+                /// once the previous one was triggered. Writing to a full queue raises
+                /// `Event::InjectedQueueOverflow` and drops the context.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `sequence` is empty.
+                pub fn start_injected(&mut self, sequence: &InjectedSequence<$ADC>) {
+                    assert!(!sequence.is_empty(), "empty ADC injected sequence");
+
+                    let (jextsel, jexten) = match self.injected_trigger {
+                        Some((jextsel, edge)) => (jextsel, edge.bitcode()),
+                        None => (0, 0b00),
+                    };
+
+                    // JL: sequence length - 1
+                    // JEXTSEL, JEXTEN: trigger
+                    // JSQ1 to JSQ4: channels, in 6-bit fields starting at bit 8
+                    let mut jsqr = (sequence.len() - 1) as u32
+                        | u32::from(jextsel) << 2
+                        | u32::from(jexten) << 6;
+                    for (i, &chan) in sequence.conversions().iter().enumerate() {
+                        jsqr |= u32::from(chan) << (8 + 6 * i);
+                    }
+
+                    // NOTE(unsafe): the channels are valid, see `InjectedSequence::add`
+                    self.rb.jsqr.write(|w| unsafe { w.bits(jsqr) });
+                    self.rb.cr.modify(|_, w| w.jadstart().set_bit());
+                }
+
+                /// Converts `sequence` once by software and waits for the results
+                ///
+                /// With an injected trigger or auto-injection, the conversions don't start
+                /// by software. Use `start_injected` and the `InjectedEndOfSequence` event
+                /// instead.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `results` is shorter than the sequence, or if an injected
+                /// trigger or auto-injection is set.
+                pub fn convert_injected(
+                    &mut self,
+                    sequence: &InjectedSequence<$ADC>,
+                    results: &mut [u16],
+                ) {
+                    assert!(results.len() >= sequence.len());
+                    let software = self.injected_trigger.is_none()
+                        && self.rb.cfgr.read().jauto().bit_is_clear();
+                    assert!(software, "injected conversions are not started by software");
+
+                    self.clear_event(Event::InjectedEndOfSequence);
+                    self.start_injected(sequence);
+                    while !self.event_occurred(Event::InjectedEndOfSequence) {}
+                    self.clear_event(Event::InjectedEndOfSequence);
+
+                    for (rank, result) in results[..sequence.len()].iter_mut().enumerate() {
+                        *result = self.read_injected(rank);
+                    }
+                }
+
+                /// Returns the result of the injected conversion of `rank` (0 to 3)
+                pub fn read_injected(&self, rank: usize) -> u16 {
+                    match rank {
+                        0 => self.rb.jdr1.read().bits() as u16,
+                        1 => self.rb.jdr2.read().bits() as u16,
+                        2 => self.rb.jdr3.read().bits() as u16,
+                        3 => self.rb.jdr4.read().bits() as u16,
+                        _ => panic!("invalid injected rank"),
+                    }
+                }
+
+                /// Stops ongoing injected conversions and flushes the context queue
+                pub fn stop_injected(&mut self) {
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.rb.cr.modify(|_, w| w.jadstp().set_bit());
+                        while self.rb.cr.read().jadstp().bit_is_set() {}
+                    }
+                }
+
+                /// Sets the handling of an empty injected context queue
+                ///
+                /// Stops all ongoing conversions.
+                pub fn set_injected_queue_mode(&mut self, mode: InjectedQueueMode) {
+                    self.stop();
+                    self.stop_injected();
+                    self.rb
+                        .cfgr
+                        .modify(|_, w| w.jqm().bit(mode == InjectedQueueMode::AllowEmpty));
+                }
+
+                /// Enables or disables the automatic injected conversion
+                ///
+                /// If enabled, the injected sequence is converted after every regular
+                /// sequence. This requires the injected sequence to be started by
+                /// software. Stops all ongoing conversions.
+                pub fn set_auto_injection(&mut self, enable: bool) {
+                    self.stop();
+                    self.stop_injected();
+                    self.rb.cfgr.modify(|_, w| w.jauto().bit(enable));
+                }
+
+                /// Guards the regular and injected conversions of all channels with analog
+                /// watchdog 1
+                ///
+                /// The 12-bit thresholds are compared to the raw results before alignment.
+                /// At lower resolutions, the bits below the resolution have to be 0. Stops
+                /// all ongoing conversions.
+                pub fn enable_watchdog1_all(&mut self, low: u16, high: u16) {
+                    self.set_watchdog1(None, low, high);
+                }
+
+                /// Guards the regular and injected conversions of the channel of `pin` with
+                /// analog watchdog 1
+                ///
+                /// See `enable_watchdog1_all`.
+                pub fn enable_watchdog1<PIN>(&mut self, _pin: &PIN, low: u16, high: u16)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.set_watchdog1(Some(PIN::channel()), low, high);
+                }
+
+                /// Disables analog watchdog 1
+                pub fn disable_watchdog1(&mut self) {
+                    self.stop();
+                    self.stop_injected();
+                    self.rb.cfgr.modify(|_, w| w.awd1en().clear_bit().jawd1en().clear_bit());
+                }
+
+                /// Guards `channels` with analog watchdog 2
+                ///
+                /// The 8-bit thresholds are compared to the 8 most significant bits of the
+                /// 12-bit results. Stops all ongoing conversions.
+                pub fn enable_watchdog2(
+                    &mut self,
+                    channels: &WatchdogChannels<$ADC>,
+                    low: u8,
+                    high: u8,
+                ) {
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): the thresholds fill their fields
+                    self.rb.tr2.write(|w| unsafe { w.bits(u32::from(low) | u32::from(high) << 16) });
+                    // NOTE(unsafe): the mask only holds valid channels
+                    self.rb.awd2cr.write(|w| unsafe { w.bits(channels.mask) });
+                }
+
+                /// Disables analog watchdog 2
+                pub fn disable_watchdog2(&mut self) {
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): no channel is guarded
+                    self.rb.awd2cr.write(|w| unsafe { w.bits(0) });
+                }
+
+                /// Guards `channels` with analog watchdog 3
+                ///
+                /// See `enable_watchdog2`.
+                pub fn enable_watchdog3(
+                    &mut self,
+                    channels: &WatchdogChannels<$ADC>,
+                    low: u8,
+                    high: u8,
+                ) {
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): the thresholds fill their fields
+                    self.rb.tr3.write(|w| unsafe { w.bits(u32::from(low) | u32::from(high) << 16) });
+                    // NOTE(unsafe): the mask only holds valid channels
+                    self.rb.awd3cr.write(|w| unsafe { w.bits(channels.mask) });
+                }
+
+                /// Disables analog watchdog 3
+                pub fn disable_watchdog3(&mut self) {
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): no channel is guarded
+                    self.rb.awd3cr.write(|w| unsafe { w.bits(0) });
+                }
+
+                fn set_watchdog1(&mut self, chan: Option<u8>, low: u16, high: u16) {
+                    assert!(low <= 0xFFF && high <= 0xFFF, "invalid watchdog threshold");
+
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): the thresholds fit into their 12-bit fields
+                    self.rb.tr1.write(|w| unsafe { w.bits(u32::from(low) | u32::from(high) << 16) });
+                    // AWD1SGL: a single channel or all channels
+                    // AWD1CH: the channel
+                    // NOTE(unsafe): chan is the x in ADCn_INx
+                    self.rb.cfgr.modify(|_, w| unsafe { w
+                        .awd1sgl().bit(chan.is_some())
+                        .awd1ch().bits(chan.unwrap_or(0))
+                        .awd1en().set_bit()
+                        .jawd1en().set_bit()
+                    });
+                }
+
+                /// Sets the alignment of the results
+                ///
+                /// Stops ongoing regular and injected conversions.
+                pub fn set_align(&mut self, align: Align) {
+                    self.stop();
+                    self.stop_injected();
                     self.rb.cfgr.modify(|_, w| w.align().bit(align.bitvalue()));
+                    self.align = align;
+                }
+
+                /// Sets the resolution
+                ///
+                /// Lower resolutions convert faster, e.g. 6 bits take 6.5 instead of 12.5
+                /// ADC clock cycles. Stops ongoing regular and injected conversions.
+                pub fn set_resolution(&mut self, resolution: Resolution) {
+                    self.stop();
+                    self.stop_injected();
+                    // NOTE(unsafe): Use only predefined, valid values.
+                    self.rb.cfgr.modify(|_, w| unsafe { w.res().bits(resolution.bitcode()) });
+                    self.resolution = resolution;
+                }
+
+                /// Sets the sample time of the channel of `pin`
+                ///
+                /// The sample time of a sequence overrides it while the sequence is
+                /// converted, one-shot conversions restore it. Stops ongoing regular
+                /// and injected conversions.
+                pub fn set_sample_time<PIN>(&mut self, _pin: &PIN, sample_time: SampleTime)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.stop();
+                    self.stop_injected();
+                    self.sample_times[usize::from(PIN::channel())] = sample_time;
+                    self.set_chan_smps(PIN::channel(), sample_time);
+                }
+
+                /// Sets the shortest sample time for the channel of `pin`, which suits a
+                /// source impedance of `ohms` at the current ADC clock and resolution
+                ///
+                /// Returns the chosen sample time. See `SampleTime::for_source_impedance`.
+                pub fn set_source_impedance<PIN>(
+                    &mut self,
+                    pin: &PIN,
+                    ohms: u32,
+                ) -> Result<SampleTime, Error>
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    let adc_clock = Hertz(self.clocks.hclk().0 / (self.prescale as u32));
+                    let sample_time =
+                        SampleTime::for_source_impedance(ohms, adc_clock, self.resolution)
+                            .ok_or(Error::SourceImpedance)?;
+                    self.set_sample_time(pin, sample_time);
+                    Ok(sample_time)
+                }
+
+                /// Wraps a result read from the ADC into a `Reading` with the current
+                /// resolution and alignment
+                pub fn reading(&self, raw: u16) -> Reading {
+                    Reading {
+                        raw,
+                        resolution: self.resolution,
+                        align: self.align,
+                    }
                 }
 
                 fn enable(&mut self) {
@@ -344,12 +1823,58 @@ macro_rules! adc_hal {
                 }
 
                 fn disable(&mut self) {
-                    self.rb.cr.modify(|_, w| w.aden().clear_bit());
+                    // ADEN is cleared by hardware, writing 0 to it has no effect
+                    if self.rb.cr.read().aden().bit_is_set() {
+                        self.rb.cr.modify(|_, w| w.aden().clear_bit().addis().set_bit());
+                        while self.rb.cr.read().aden().bit_is_set() {}
+                    }
+                }
+
+                /// Enables or disables the differential conversion of `input`
+                ///
+                /// While enabled, the channel of the negative input can't be converted
+                /// single ended. The results are interpreted with `Reading::signed_value`.
+                /// Stops all ongoing conversions, as the ADC has to be disabled for this.
+                pub fn set_differential<P, N>(
+                    &mut self,
+                    _input: &Differential<P, N>,
+                    enable: bool,
+                ) where
+                    Differential<P, N>: Channel<$ADC, ID = u8>,
+                {
+                    let chan = <Differential<P, N> as Channel<$ADC>>::channel();
+
+                    self.stop();
+                    self.stop_injected();
+                    self.disable();
+                    // NOTE(unsafe): chan is the x in ADCn_INx
+                    self.rb.difsel.modify(|r, w| unsafe {
+                        let bits = if enable {
+                            r.bits() | 1 << chan
+                        } else {
+                            r.bits() & !(1 << chan)
+                        };
+                        w.bits(bits)
+                    });
+                    self.enable();
+                }
+
+                /// Calibrates the differential conversions
+                ///
+                /// The single ended calibration is performed on initialization, the
+                /// calibration factors of both are kept separately. Stops all ongoing
+                /// conversions.
+                pub fn calibrate_differential(&mut self) {
+                    self.stop();
+                    self.stop_injected();
+                    self.calibrate(true);
+                    self.wait_adc_clk_cycles(4);
+                    self.enable();
                 }
 
 
                 /// Calibrate according to 15.3.8 in the Reference Manual
-                fn calibrate(&mut self) {
+                fn calibrate(&mut self, differential: bool) {
                     if !self.rb.cr.read().advregen().is_enabled() {
                         self.advregen_enable();
                         self.wait_advregen_startup();
@@ -358,8 +1883,7 @@ macro_rules! adc_hal {
                     self.disable();
 
                     self.rb.cr.modify(|_, w| w
-                        // NOTE: needs to be adopted if implementing differential input
-                        .adcaldif().clear_bit()
+                        .adcaldif().bit(differential)
                         .adcal()   .set_bit());
 
                     while self.rb.cr.read().adcal().bit_is_set() {}
@@ -388,7 +1912,7 @@ macro_rules! adc_hal {
                 /// busy ADC read
                 fn convert_one(&mut self, chan: u8) -> u16 {
                     self.ensure_oneshot();
-                    self.set_chan_smps(chan, SampleTime::default());
+                    self.set_chan_smps(chan, self.sample_times[usize::from(chan)]);
                     self.select_single_chan(chan);
 
                     self.rb.cr.modify(|_, w| w.adstart().set_bit());
@@ -417,8 +1941,8 @@ macro_rules! adc_hal {
                     );
                 }
 
-                /// Note: only allowed when ADSTART = 0
-                fn set_chan_smps(&self, chan: u8, smp: SampleTime) {
+                /// Note: only allowed when ADSTART = 0 and JADSTART = 0
+                fn set_chan_smps(&mut self, chan: u8, smp: SampleTime) {
                     match chan {
                         1 => self.rb.smpr1.modify(|_, w| w.smp1().bits(smp.bitcode())),
                         2 => self.rb.smpr1.modify(|_, w| w.smp2().bits(smp.bitcode())),
@@ -429,7 +1953,8 @@ macro_rules! adc_hal {
                         7 => self.rb.smpr1.modify(|_, w| w.smp7().bits(smp.bitcode())),
                         8 => self.rb.smpr1.modify(|_, w| w.smp8().bits(smp.bitcode())),
                         9 => self.rb.smpr1.modify(|_, w| w.smp9().bits(smp.bitcode())),
-                        11 => self.rb.smpr2.modify(|_, w| w.smp10().bits(smp.bitcode())),
+                        10 => self.rb.smpr2.modify(|_, w| w.smp10().bits(smp.bitcode())),
+                        11 => self.rb.smpr2.modify(|_, w| w.smp11().bits(smp.bitcode())),
                         12 => self.rb.smpr2.modify(|_, w| w.smp12().bits(smp.bitcode())),
                         13 => self.rb.smpr2.modify(|_, w| w.smp13().bits(smp.bitcode())),
                         14 => self.rb.smpr2.modify(|_, w| w.smp14().bits(smp.bitcode())),
@@ -455,13 +1980,38 @@ macro_rules! adc_hal {
                         return Ok(res.into());
                     }
                 }
+
+            impl dma::Target for Adc<$ADC> {
+                type Error = Error;
+
+                fn enable_dma(&mut self) {
+                    self.clear_event(Event::Overrun);
+                    self.rb.cfgr.modify(|_, w| w.dmaen().set_bit());
+                    self.rb.cr.modify(|_, w| w.adstart().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.stop();
+                    self.rb.cfgr.modify(|_, w| w.dmaen().clear_bit());
+                }
+
+                /// Reports an overrun, which stopped the DMA requests as the old results are
+                /// preserved
+                fn check_error(&mut self) -> Result<(), Error> {
+                    if self.overrun_mode == OverrunMode::Preserve
+                        && self.event_occurred(Event::Overrun)
+                    {
+                        Err(Error::Overrun)
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
         )+
     }
 }
 
 /// Macro to implement ADC functionallity for ADC1 and ADC2
-///
-/// TODO: Extend/differentiate beyond f303.
 macro_rules! adc12_hal {
     ($(
             $ADC:ident: ($adcx:ident),
@@ -469,6 +2019,9 @@ macro_rules! adc12_hal {
         $(
             impl Adc<$ADC> {
                 fn enable_clock(&self, ahb: &mut AHB, adc_common: &mut ADC1_2) {
+                    #[cfg(feature = "stm32f301")]
+                    ahb.enr().modify(|_, w| w.adc1en().enabled());
+                    #[cfg(not(feature = "stm32f301"))]
                     ahb.enr().modify(|_, w| w.adc12en().enabled());
                     // NOTE(unsafe): Use only predefined, valid values.
                     unsafe {
@@ -479,20 +2032,20 @@ macro_rules! adc12_hal {
                 }
             }
             adc_hal! {
-                $ADC: ($adcx, ADC1_2),
+                $ADC: ($adcx, ADC1_2, Adc12Trigger, Adc12InjectedTrigger),
             }
         )+
     }
 }
 
 /// Macro to implement ADC functionallity for ADC3 and ADC4
-///
-/// TODO: Extend/differentiate beyond f303.
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 macro_rules! adc34_hal {
     ($(
@@ -511,24 +2064,326 @@ macro_rules! adc34_hal {
                 }
             }
             adc_hal! {
-                $ADC: ($adcx, ADC3_4),
+                $ADC: ($adcx, ADC3_4, Adc34Trigger, Adc34InjectedTrigger),
             }
         )+
     }
 }
 
-#[cfg(feature = "stm32f303")]
 adc12_hal! {
     ADC1: (adc1),
+}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+adc12_hal! {
     ADC2: (adc2),
 }
+
 #[cfg(any(
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
 ))]
 adc34_hal! {
     ADC3: (adc3),
     ADC4: (adc4),
 }
+
+/// Macro to implement the dual mode of a pair of ADCs
+macro_rules! dual_hal {
+    ($(
+        $ADC_COMMON:ident: ($adcxy:ident, $MASTER:ident, $SLAVE:ident),
+    )+) => {
+        $(
+            impl DualAdc<$MASTER, $SLAVE, $ADC_COMMON> {
+                /// Combines the ADCs of a pair in `mode`
+                ///
+                /// Stops all ongoing conversions.
+                pub fn $adcxy(
+                    mut master: Adc<$MASTER>,
+                    mut slave: Adc<$SLAVE>,
+                    adc_common: $ADC_COMMON,
+                    mode: DualMode,
+                ) -> Self {
+                    master.stop();
+                    master.stop_injected();
+                    slave.stop();
+                    slave.stop_injected();
+
+                    // DUAL: dual mode
+                    // DELAY: delay between interleaved sampling phases
+                    // NOTE(unsafe): Use only predefined, valid values.
+                    adc_common.ccr.modify(|_, w| unsafe { w
+                        .dual().bits(mode.bitcode())
+                        .delay().bits(mode.delay() - 1)
+                    });
+
+                    DualAdc {
+                        master,
+                        slave,
+                        common: adc_common,
+                    }
+                }
+
+                /// Programs both regular sequences and starts converting them once
+                pub fn start_sequence(
+                    &mut self,
+                    master: &Sequence<$MASTER>,
+                    slave: &Sequence<$SLAVE>,
+                ) {
+                    self.slave.set_sequence(slave);
+                    self.master.start_sequence(master);
+                }
+
+                /// Programs both regular sequences and converts them continuously
+                pub fn start_continuous(
+                    &mut self,
+                    master: &Sequence<$MASTER>,
+                    slave: &Sequence<$SLAVE>,
+                ) {
+                    // the slave converts along with the master, so it follows its trigger
+                    let continuous = self.master.external_trigger.is_none();
+                    self.slave.program_sequence(slave, continuous);
+                    self.master.start_continuous(master);
+                }
+
+                /// Returns the results of the last regular conversions of the master and the
+                /// slave
+                pub fn read_data(&mut self) -> (u16, u16) {
+                    Self::split(self.common.cdr.read().bits())
+                }
+
+                /// Converts both sequences, while the DMA moves the packed results into
+                /// `buffer`
+                ///
+                /// The DMA request of the master is used. The transfer is complete once
+                /// `buffer` is full.
+                ///
+                /// # Panics
+                ///
+                /// Panics if either ADC converts with 8 or 6 bits, as the packed results
+                /// take only 16 bits then.
+                pub fn read_dma<C>(
+                    mut self,
+                    master: &Sequence<$MASTER>,
+                    slave: &Sequence<$SLAVE>,
+                    buffer: &'static mut [u32],
+                    mut channel: C,
+                ) -> dma::Transfer<&'static mut [u32], C, Self>
+                where
+                    C: dma::RxChannel<$MASTER>,
+                {
+                    self.assert_dma_resolution();
+                    self.slave.set_sequence(slave);
+                    self.master.set_sequence(master);
+                    // DMACFG: one shot mode, the requests stop once the transfer is complete
+                    self.common.ccr.modify(|_, w| w.dmacfg().clear_bit());
+                    self.set_dma_address(&mut channel);
+
+                    dma::Transfer::start_read(buffer, channel, self)
+                }
+
+                /// Converts both sequences continuously, while the DMA fills both halves of
+                /// `buffer` with the packed results in turn
+                ///
+                /// See `Adc::read_dma_circular`.
+                ///
+                /// # Panics
+                ///
+                /// Panics if either ADC converts with 8 or 6 bits, as the packed results
+                /// take only 16 bits then.
+                pub fn read_dma_circular<C>(
+                    mut self,
+                    master: &Sequence<$MASTER>,
+                    slave: &Sequence<$SLAVE>,
+                    buffer: &'static mut [u32],
+                    mut channel: C,
+                ) -> dma::CircBuffer<u32, C, Self>
+                where
+                    C: dma::RxChannel<$MASTER>,
+                {
+                    self.assert_dma_resolution();
+                    let continuous = self.master.external_trigger.is_none();
+                    self.slave.program_sequence(slave, continuous);
+                    self.master.program_sequence(master, true);
+                    // DMACFG: circular mode, the requests go on after the last transfer
+                    self.common.ccr.modify(|_, w| w.dmacfg().set_bit());
+                    self.set_dma_address(&mut channel);
+
+                    dma::CircBuffer::start_read(buffer, channel, self)
+                }
+
+                /// Switches both ADCs back to independent mode and releases them
+                ///
+                /// Stops all ongoing conversions.
+                pub fn free(mut self) -> (Adc<$MASTER>, Adc<$SLAVE>, $ADC_COMMON) {
+                    self.master.stop();
+                    self.master.stop_injected();
+                    self.slave.stop();
+                    self.slave.stop_injected();
+                    // NOTE(unsafe): 0 selects the independent mode
+                    self.common.ccr.modify(|_, w| unsafe { w.dual().bits(0b00000) });
+
+                    (self.master, self.slave, self.common)
+                }
+
+                /// MDMA = 0b10 transfers the results of 12 and 10 bits as a 32-bit word,
+                /// results of 8 and 6 bits would need MDMA = 0b11 and 16-bit words
+                fn assert_dma_resolution(&self) {
+                    let wide = |resolution| match resolution {
+                        Resolution::Bits12 | Resolution::Bits10 => true,
+                        Resolution::Bits8 | Resolution::Bits6 => false,
+                    };
+                    assert!(
+                        wide(self.master.resolution) && wide(self.slave.resolution),
+                        "dual ADC DMA needs a resolution of 12 or 10 bits"
+                    );
+                }
+
+                fn set_dma_address<C: dma::Channel>(&self, channel: &mut C) {
+                    // NOTE(unsafe) CDR holds both 16-bit results
+                    unsafe {
+                        channel.set_peripheral_address(
+                            &self.common.cdr as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+                }
+            }
+
+            impl dma::Target for DualAdc<$MASTER, $SLAVE, $ADC_COMMON> {
+                type Error = Error;
+
+                fn enable_dma(&mut self) {
+                    self.master.clear_event(Event::Overrun);
+                    self.slave.clear_event(Event::Overrun);
+                    // MDMA: one request per pair of results of 12 or 10 bits
+                    // NOTE(unsafe): Use only predefined, valid values.
+                    self.common.ccr.modify(|_, w| unsafe { w.mdma().bits(0b10) });
+                    self.master.rb.cr.modify(|_, w| w.adstart().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.master.stop();
+                    // NOTE(unsafe): 0 disables the requests
+                    self.common.ccr.modify(|_, w| unsafe { w.mdma().bits(0b00) });
+                }
+
+                /// Reports an overrun of either ADC, which stopped the DMA requests as the
+                /// old results are preserved
+                fn check_error(&mut self) -> Result<(), Error> {
+                    let overrun = self.master.event_occurred(Event::Overrun)
+                        || self.slave.event_occurred(Event::Overrun);
+
+                    if self.master.overrun_mode == OverrunMode::Preserve && overrun {
+                        Err(Error::Overrun)
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+        )+
+    }
+}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+dual_hal! {
+    ADC1_2: (adc12, ADC1, ADC2),
+}
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+dual_hal! {
+    ADC3_4: (adc34, ADC3, ADC4),
+}
+
+unsafe impl dma::RxChannel<ADC1> for dma1::C1 {}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+unsafe impl dma::RxChannel<ADC2> for dma::dma2::C1 {}
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+unsafe impl dma::RxChannel<ADC3> for dma::dma2::C5 {}
+
+#[cfg(any(
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398",
+))]
+unsafe impl dma::RxChannel<ADC4> for dma::dma2::C2 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regular_cfgr_sets_cont_only_without_external_trigger() {
+        assert_eq!(regular_cfgr(None, false), (false, 0, 0b00));
+        assert_eq!(regular_cfgr(None, true), (true, 0, 0b00));
+
+        let trigger = Some((0, TriggerEdge::Rising));
+        assert_eq!(regular_cfgr(trigger, false), (false, 0, 0b01));
+        assert_eq!(regular_cfgr(trigger, true), (false, 0, 0b01));
+
+        let trigger = Some((11, TriggerEdge::Both));
+        assert_eq!(regular_cfgr(trigger, true), (false, 11, 0b11));
+    }
+
+    #[test]
+    fn for_source_impedance() {
+        let adc_clock = Hertz(72_000_000);
+
+        assert_eq!(
+            SampleTime::for_source_impedance(0, adc_clock, Resolution::Bits12),
+            Some(SampleTime::T_4)
+        );
+        assert_eq!(
+            SampleTime::for_source_impedance(u32::MAX, adc_clock, Resolution::Bits12),
+            None
+        );
+        assert_eq!(
+            SampleTime::for_source_impedance(u32::MAX, Hertz(u32::MAX), Resolution::Bits12),
+            None
+        );
+    }
+}
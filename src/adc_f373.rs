@@ -0,0 +1,415 @@
+//! API for the ADC (Analog to Digital Converter) of the STM32F373
+//!
+//! Unlike the ADCs of the other STM32F3 devices, the ADC of this line is the
+//! one of the STM32F1: a single 12-bit ADC1 clocked from APB2 through ADCPRE
+//! (see `rcc::CFGR::adcclk`), with one shot and scan (sequence) conversions.
+//! Its API differs from the one of the `adc` module, e.g. in the sample times, so
+//! it has a module of its own.
+//!
+//! For the 16-bit sigma-delta converters see the `sdadc` module.
+use core::marker::PhantomData;
+
+use embedded_hal::adc::{Channel, OneShot};
+use void::Void;
+
+use crate::dma::{self, dma1};
+use crate::gpio::{gpioa, gpiob, gpioc, Analog};
+use crate::rcc::{Clocks, APB2};
+use crate::stm32::ADC1;
+
+/// ADC sampling time
+///
+/// The number in the variant name is the number of ADC clock cycles,
+/// where the trailing `5` stands for half a cycle (e.g. `T_239_5` = 239.5 cycles).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SampleTime {
+    T_1_5,
+    T_7_5,
+    T_13_5,
+    T_28_5,
+    T_41_5,
+    T_55_5,
+    T_71_5,
+    T_239_5,
+}
+
+impl SampleTime {
+    /// Default: 1.5 cycles
+    pub fn default() -> Self {
+        SampleTime::T_1_5
+    }
+
+    /// Conversion to bits for SMPx in ADC_SMPRx
+    fn bitcode(&self) -> u8 {
+        match self {
+            SampleTime::T_1_5 => 0b000,
+            SampleTime::T_7_5 => 0b001,
+            SampleTime::T_13_5 => 0b010,
+            SampleTime::T_28_5 => 0b011,
+            SampleTime::T_41_5 => 0b100,
+            SampleTime::T_55_5 => 0b101,
+            SampleTime::T_71_5 => 0b110,
+            SampleTime::T_239_5 => 0b111,
+        }
+    }
+}
+
+/// ADC data register alignment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    /// Right alignment of output data
+    Right,
+    /// Left alignment of output data
+    Left,
+}
+
+impl Align {
+    /// Default: right alignment
+    pub fn default() -> Self {
+        Align::Right
+    }
+
+    /// Conversion to the bit value of ALIGN in ADC_CR2
+    fn bitvalue(&self) -> bool {
+        match self {
+            Align::Right => false,
+            Align::Left => true,
+        }
+    }
+}
+
+/// A sequence of regular conversions
+///
+/// The sample time is configured per channel, so if a channel appears twice,
+/// the one added last applies to all of its conversions.
+pub struct Sequence<ADC> {
+    channels: [(u8, SampleTime); 16],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Sequence<ADC> {
+    /// Creates an empty sequence
+    pub fn new() -> Self {
+        Sequence {
+            channels: [(0, SampleTime::default()); 16],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a conversion of `pin`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already holds 16 conversions.
+    pub fn add<PIN>(mut self, _pin: &PIN, sample_time: SampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 16, "ADC sequence is full");
+        self.channels[self.len] = (PIN::channel(), sample_time);
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of conversions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence holds no conversions
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn conversions(&self) -> &[(u8, SampleTime)] {
+        &self.channels[..self.len]
+    }
+}
+
+impl<ADC> Default for Sequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Analog to Digital Converter
+pub struct Adc<ADC> {
+    rb: ADC,
+    clocks: Clocks,
+}
+
+macro_rules! adc_pins {
+    ($ADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
+        $(
+            impl Channel<$ADC> for $pin {
+                type ID = u8;
+
+                fn channel() -> u8 { $chan }
+            }
+        )+
+    };
+}
+
+// # ADC1 Pin/Channel mapping
+
+adc_pins!(ADC1,
+    gpioa::PA0<Analog> => 0_u8,
+    gpioa::PA1<Analog> => 1_u8,
+    gpioa::PA2<Analog> => 2_u8,
+    gpioa::PA3<Analog> => 3_u8,
+    gpioa::PA4<Analog> => 4_u8,
+    gpioa::PA5<Analog> => 5_u8,
+    gpioa::PA6<Analog> => 6_u8,
+    gpioa::PA7<Analog> => 7_u8,
+    gpiob::PB0<Analog> => 8_u8,
+    gpiob::PB1<Analog> => 9_u8,
+    gpioc::PC0<Analog> => 10_u8,
+    gpioc::PC1<Analog> => 11_u8,
+    gpioc::PC2<Analog> => 12_u8,
+    gpioc::PC3<Analog> => 13_u8,
+    gpioc::PC4<Analog> => 14_u8,
+    gpioc::PC5<Analog> => 15_u8,
+);
+
+/// Internal temperature sensor (ADC1_IN16)
+///
+/// Enabled together with `VRefInt` by `Adc::enable_internal_channels`.
+pub struct TemperatureSensor;
+
+/// Internal reference voltage (ADC1_IN17)
+///
+/// Enabled together with `TemperatureSensor` by `Adc::enable_internal_channels`.
+pub struct VRefInt;
+
+adc_pins!(ADC1,
+    TemperatureSensor => 16_u8,
+    VRefInt => 17_u8,
+);
+
+impl Adc<ADC1> {
+    /// Init a new ADC
+    ///
+    /// Enables the clock, powers the ADC up and performs a calibration
+    pub fn adc1(rb: ADC1, apb2: &mut APB2, clocks: Clocks) -> Self {
+        apb2.enr().modify(|_, w| w.adcen().set_bit());
+        apb2.rstr().modify(|_, w| w.adcrst().set_bit());
+        apb2.rstr().modify(|_, w| w.adcrst().clear_bit());
+
+        let mut adc = Self { rb, clocks };
+        // NOTE(unsafe) EXTSEL = 0b111 selects the software start SWSTART
+        adc.rb
+            .cr2
+            .write(|w| unsafe { w.extsel().bits(0b111).exttrig().set_bit().adon().set_bit() });
+        // t_STAB: the ADC needs a stabilization time before its calibration
+        adc.wait_adc_clk_cycles(2);
+        adc.calibrate();
+        adc
+    }
+
+    /// Runs the calibration and waits for it to complete
+    ///
+    /// The calibration must be repeated after the ADC has been powered down.
+    pub fn calibrate(&mut self) {
+        // RSTCAL and CAL are cleared by hardware once done
+        self.rb.cr2.modify(|_, w| w.rstcal().set_bit());
+        while self.rb.cr2.read().rstcal().bit_is_set() {}
+        self.rb.cr2.modify(|_, w| w.cal().set_bit());
+        while self.rb.cr2.read().cal().bit_is_set() {}
+    }
+
+    /// Sets the alignment of the conversion results
+    pub fn set_align(&mut self, align: Align) {
+        self.rb.cr2.modify(|_, w| w.align().bit(align.bitvalue()));
+    }
+
+    /// Sets the sample time of `pin` for all conversions from now on
+    pub fn set_sample_time<PIN>(&mut self, _pin: &PIN, sample_time: SampleTime)
+    where
+        PIN: Channel<ADC1, ID = u8>,
+    {
+        self.set_chan_smps(PIN::channel(), sample_time);
+    }
+
+    /// Enables the temperature sensor and the internal reference voltage
+    ///
+    /// The inputs are available after a startup time of 10 µs.
+    pub fn enable_internal_channels(&mut self) -> (TemperatureSensor, VRefInt) {
+        self.rb.cr2.modify(|_, w| w.tsvrefe().set_bit());
+        (TemperatureSensor, VRefInt)
+    }
+
+    /// Disables the temperature sensor and the internal reference voltage
+    pub fn disable_internal_channels(&mut self, _: TemperatureSensor, _: VRefInt) {
+        self.rb.cr2.modify(|_, w| w.tsvrefe().clear_bit());
+    }
+
+    /// Converts the sequence once and writes the results into `buffer`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is shorter than the sequence.
+    pub fn convert_sequence(&mut self, sequence: &Sequence<ADC1>, buffer: &mut [u16]) {
+        assert!(buffer.len() >= sequence.len());
+        // Without DMA each conversion of the scan is read on its own EOC
+        for (&(chan, smp), result) in sequence.conversions().iter().zip(buffer.iter_mut()) {
+            self.set_chan_smps(chan, smp);
+            *result = self.convert_one(chan);
+        }
+    }
+
+    /// Converts the sequence once, while the DMA writes the results into `buffer`
+    ///
+    /// The transfer is complete once `buffer` is full, so it has to be as long as the
+    /// sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `buffer` differs from the length of the sequence.
+    pub fn read_dma<C>(
+        mut self,
+        sequence: &Sequence<ADC1>,
+        buffer: &'static mut [u16],
+        mut channel: C,
+    ) -> dma::Transfer<&'static mut [u16], C, Self>
+    where
+        C: dma::RxChannel<ADC1>,
+    {
+        assert_eq!(buffer.len(), sequence.len());
+        self.set_sequence(sequence);
+        // NOTE(unsafe) DR holds a 16-bit result
+        unsafe {
+            channel.set_peripheral_address(&self.rb.dr as *const _ as u32, dma::Increment::Disable);
+        }
+
+        dma::Transfer::start_read(buffer, channel, self)
+    }
+
+    /// Returns the result of the last regular conversion
+    pub fn read_data(&mut self) -> u16 {
+        self.rb.dr.read().data().bits()
+    }
+
+    /// Powers the ADC down and releases the peripheral
+    pub fn free(self) -> ADC1 {
+        self.rb.cr2.modify(|_, w| w.adon().clear_bit());
+        self.rb
+    }
+
+    fn set_sequence(&mut self, sequence: &Sequence<ADC1>) {
+        assert!(!sequence.is_empty());
+        // NOTE(unsafe) L holds the sequence length - 1
+        self.rb
+            .sqr1
+            .modify(|_, w| unsafe { w.l().bits((sequence.len() - 1) as u8) });
+        for (i, &(chan, smp)) in sequence.conversions().iter().enumerate() {
+            self.set_chan_smps(chan, smp);
+            self.set_sq(i + 1, chan);
+        }
+        self.rb.cr1.modify(|_, w| w.scan().set_bit());
+    }
+
+    fn convert_one(&mut self, chan: u8) -> u16 {
+        // NOTE(unsafe) a sequence of a single conversion
+        self.rb.sqr1.modify(|_, w| unsafe { w.l().bits(0) });
+        self.set_sq(1, chan);
+        self.rb.cr1.modify(|_, w| w.scan().clear_bit());
+        self.rb.cr2.modify(|_, w| w.swstart().set_bit());
+        while self.rb.sr.read().eoc().bit_is_clear() {}
+        // reading DR clears EOC
+        self.read_data()
+    }
+
+    /// Sets the channel of the `rank`th conversion of the regular sequence
+    fn set_sq(&mut self, rank: usize, chan: u8) {
+        // NOTE(unsafe) chan is the x in ADC1_INx
+        unsafe {
+            match rank {
+                1 => self.rb.sqr3.modify(|_, w| w.sq1().bits(chan)),
+                2 => self.rb.sqr3.modify(|_, w| w.sq2().bits(chan)),
+                3 => self.rb.sqr3.modify(|_, w| w.sq3().bits(chan)),
+                4 => self.rb.sqr3.modify(|_, w| w.sq4().bits(chan)),
+                5 => self.rb.sqr3.modify(|_, w| w.sq5().bits(chan)),
+                6 => self.rb.sqr3.modify(|_, w| w.sq6().bits(chan)),
+                7 => self.rb.sqr2.modify(|_, w| w.sq7().bits(chan)),
+                8 => self.rb.sqr2.modify(|_, w| w.sq8().bits(chan)),
+                9 => self.rb.sqr2.modify(|_, w| w.sq9().bits(chan)),
+                10 => self.rb.sqr2.modify(|_, w| w.sq10().bits(chan)),
+                11 => self.rb.sqr2.modify(|_, w| w.sq11().bits(chan)),
+                12 => self.rb.sqr2.modify(|_, w| w.sq12().bits(chan)),
+                13 => self.rb.sqr1.modify(|_, w| w.sq13().bits(chan)),
+                14 => self.rb.sqr1.modify(|_, w| w.sq14().bits(chan)),
+                15 => self.rb.sqr1.modify(|_, w| w.sq15().bits(chan)),
+                16 => self.rb.sqr1.modify(|_, w| w.sq16().bits(chan)),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn set_chan_smps(&mut self, chan: u8, smp: SampleTime) {
+        let smp = smp.bitcode();
+        // NOTE(unsafe) Use only predefined, valid values.
+        unsafe {
+            match chan {
+                0 => self.rb.smpr2.modify(|_, w| w.smp0().bits(smp)),
+                1 => self.rb.smpr2.modify(|_, w| w.smp1().bits(smp)),
+                2 => self.rb.smpr2.modify(|_, w| w.smp2().bits(smp)),
+                3 => self.rb.smpr2.modify(|_, w| w.smp3().bits(smp)),
+                4 => self.rb.smpr2.modify(|_, w| w.smp4().bits(smp)),
+                5 => self.rb.smpr2.modify(|_, w| w.smp5().bits(smp)),
+                6 => self.rb.smpr2.modify(|_, w| w.smp6().bits(smp)),
+                7 => self.rb.smpr2.modify(|_, w| w.smp7().bits(smp)),
+                8 => self.rb.smpr2.modify(|_, w| w.smp8().bits(smp)),
+                9 => self.rb.smpr2.modify(|_, w| w.smp9().bits(smp)),
+                10 => self.rb.smpr1.modify(|_, w| w.smp10().bits(smp)),
+                11 => self.rb.smpr1.modify(|_, w| w.smp11().bits(smp)),
+                12 => self.rb.smpr1.modify(|_, w| w.smp12().bits(smp)),
+                13 => self.rb.smpr1.modify(|_, w| w.smp13().bits(smp)),
+                14 => self.rb.smpr1.modify(|_, w| w.smp14().bits(smp)),
+                15 => self.rb.smpr1.modify(|_, w| w.smp15().bits(smp)),
+                16 => self.rb.smpr1.modify(|_, w| w.smp16().bits(smp)),
+                17 => self.rb.smpr1.modify(|_, w| w.smp17().bits(smp)),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn wait_adc_clk_cycles(&self, cycles: u32) {
+        let adc_per_cpu_cycles = self.clocks.sysclk().0 / self.clocks.adcclk().0;
+        cortex_m::asm::delay(adc_per_cpu_cycles * cycles);
+    }
+}
+
+impl<WORD, PIN> OneShot<ADC1, WORD, PIN> for Adc<ADC1>
+where
+    WORD: From<u16>,
+    PIN: Channel<ADC1, ID = u8>,
+{
+    type Error = ();
+
+    fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+        let res = self.convert_one(PIN::channel());
+        Ok(res.into())
+    }
+}
+
+impl dma::Target for Adc<ADC1> {
+    type Error = Void;
+
+    fn enable_dma(&mut self) {
+        // DMA requests on each conversion, then a software start
+        self.rb.cr2.modify(|_, w| w.dma().set_bit());
+        self.rb.cr2.modify(|_, w| w.swstart().set_bit());
+    }
+
+    fn disable_dma(&mut self) {
+        // no further conversions, then no further DMA requests
+        self.rb
+            .cr2
+            .modify(|_, w| w.cont().clear_bit().swstart().clear_bit());
+        self.rb.cr2.modify(|_, w| w.dma().clear_bit());
+    }
+}
+
+unsafe impl dma::RxChannel<ADC1> for dma1::C1 {}
@@ -0,0 +1,779 @@
+//! Direct memory access (DMA) controller
+//!
+//! A DMA controller is split into its channels with `DmaExt::split`. Peripheral drivers
+//! point a channel at their data register and hand it to a `Transfer`, which owns the
+//! buffer, the channel and the peripheral until the transfer is finished.
+//!
+//! For continuous streams, `CircBuffer` runs the DMA in circular mode over a double buffer.
+//!
+//! Which channel serves which peripheral request is fixed by the hardware, see
+//! "DMA request mapping" in the reference manual. This is encoded by the `TxChannel` and
+//! `RxChannel` traits, which are implemented in the peripheral modules.
+
+use core::sync::atomic::{self, Ordering};
+
+use nb;
+
+use crate::rcc::AHB;
+
+/// Extension trait to split a DMA peripheral into independent channels
+pub trait DmaExt {
+    /// The type to split the DMA into
+    type Channels;
+
+    /// Splits the DMA peripheral into independent channels
+    fn split(self, ahb: &mut AHB) -> Self::Channels;
+}
+
+/// DMA error
+///
+/// `E` is the error type of the peripheral, see `Target::check_error`.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Both halves of a circular buffer were completed by the DMA before one of them was
+    /// processed
+    Overrun,
+    /// A bus error aborted the transfer
+    Transfer,
+    /// The peripheral reported an error, which stops its DMA requests
+    Target(E),
+    #[doc(hidden)]
+    _Extensible,
+}
+
+/// The resources of a `Transfer`, returned along with the error that aborted it
+pub type TransferError<B, C, T> = (Error<<T as Target>::Error>, (B, C, T));
+
+mod private {
+    /// Restricts `Channel` to the DMA channels of this crate
+    pub trait Sealed {}
+}
+
+/// DMA channel serving the transmit request of `PER`
+///
+/// Implemented in the peripheral modules for the channels of this crate, as `Channel`
+/// is sealed.
+pub unsafe trait TxChannel<PER>: Channel {}
+
+/// DMA channel serving the receive request of `PER`
+///
+/// Implemented in the peripheral modules for the channels of this crate, as `Channel`
+/// is sealed.
+pub unsafe trait RxChannel<PER>: Channel {}
+
+/// Peripheral, which can issue DMA requests
+pub trait Target {
+    /// Error of the peripheral, which can stop a running transfer
+    type Error;
+
+    /// Enables the DMA requests of the peripheral
+    fn enable_dma(&mut self);
+
+    /// Disables the DMA requests of the peripheral
+    fn disable_dma(&mut self);
+
+    /// Returns an error, if the peripheral stopped issuing DMA requests
+    ///
+    /// This is checked while waiting for a transfer, as the DMA would never complete.
+    fn check_error(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Word size of a DMA transfer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WordSize {
+    /// 8 bits
+    Bits8,
+    /// 16 bits
+    Bits16,
+    /// 32 bits
+    Bits32,
+}
+
+impl WordSize {
+    /// Conversion to bits for PSIZE and MSIZE in DMA_CCRx
+    fn bitcode(self) -> u8 {
+        match self {
+            WordSize::Bits8 => 0b00,
+            WordSize::Bits16 => 0b01,
+            WordSize::Bits32 => 0b10,
+        }
+    }
+}
+
+/// Memory word, which can be moved by the DMA
+pub unsafe trait Word: Copy {
+    /// Returns the size of this word
+    fn size() -> WordSize;
+}
+
+unsafe impl Word for u8 {
+    fn size() -> WordSize {
+        WordSize::Bits8
+    }
+}
+
+unsafe impl Word for u16 {
+    fn size() -> WordSize {
+        WordSize::Bits16
+    }
+}
+
+unsafe impl Word for u32 {
+    fn size() -> WordSize {
+        WordSize::Bits32
+    }
+}
+
+/// Channel priority level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priority {
+    /// Low
+    Low,
+    /// Medium
+    Medium,
+    /// High
+    High,
+    /// Very high
+    VeryHigh,
+}
+
+impl Priority {
+    /// Conversion to bits for PL in DMA_CCRx
+    fn bitcode(self) -> u8 {
+        match self {
+            Priority::Low => 0b00,
+            Priority::Medium => 0b01,
+            Priority::High => 0b10,
+            Priority::VeryHigh => 0b11,
+        }
+    }
+}
+
+/// Transfer direction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Read from the peripheral, write to memory
+    FromPeripheral,
+    /// Read from memory, write to the peripheral
+    FromMemory,
+}
+
+/// Address increment mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Increment {
+    /// Increment the address after each word
+    Enable,
+    /// Keep the address fixed
+    Disable,
+}
+
+/// Channel interrupt event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Half of the transfer is done
+    HalfTransfer,
+    /// The transfer is done
+    TransferComplete,
+    /// A bus error occurred during the transfer
+    TransferError,
+    /// Any of the above events occurred
+    Any,
+}
+
+impl Event {
+    /// Position of the flag in DMA_ISR and DMA_IFCR relative to the channel's flag group
+    fn offset(self) -> u32 {
+        match self {
+            Event::Any => 0,
+            Event::TransferComplete => 1,
+            Event::HalfTransfer => 2,
+            Event::TransferError => 3,
+        }
+    }
+}
+
+/// DMA channel
+///
+/// This trait is sealed, it is only implemented by the channels of `DmaExt::split`.
+pub trait Channel: private::Sealed {
+    /// Sets the address of the peripheral data register
+    ///
+    /// # Safety
+    ///
+    /// `pa` must be the address of a peripheral data register, which is valid for the
+    /// transfer direction and word size the channel is configured for.
+    unsafe fn set_peripheral_address(&mut self, pa: u32, inc: Increment);
+
+    /// Sets the memory address
+    ///
+    /// # Safety
+    ///
+    /// `ma` must point to memory, which stays valid and is not accessed otherwise for
+    /// the whole duration of the transfer.
+    unsafe fn set_memory_address(&mut self, ma: u32, inc: Increment);
+
+    /// Sets the number of words to transfer
+    fn set_transfer_length(&mut self, len: u16);
+
+    /// Returns the number of words, which are still left to transfer
+    fn remaining_transfers(&self) -> u16;
+
+    /// Sets the word size of both the peripheral and the memory side
+    fn set_word_size(&mut self, size: WordSize);
+
+    /// Sets the priority level of the channel
+    fn set_priority_level(&mut self, priority: Priority);
+
+    /// Sets the transfer direction
+    fn set_direction(&mut self, direction: Direction);
+
+    /// Enables or disables the circular mode
+    fn set_circular(&mut self, circular: bool);
+
+    /// Starts listening for an interrupt event
+    fn listen(&mut self, event: Event);
+
+    /// Stops listening for an interrupt event
+    fn unlisten(&mut self, event: Event);
+
+    /// Returns `true` if `event` occurred since it was cleared the last time
+    fn event_occurred(&self, event: Event) -> bool;
+
+    /// Clears the flag of `event`
+    fn clear_event(&mut self, event: Event);
+
+    /// Enables the channel
+    fn enable(&mut self);
+
+    /// Disables the channel
+    fn disable(&mut self);
+
+    /// Returns `true` if the channel is enabled
+    fn is_enabled(&self) -> bool;
+}
+
+struct TransferInner<B, C, T> {
+    buffer: B,
+    channel: C,
+    target: T,
+}
+
+impl<B, C: Channel, T: Target> TransferInner<B, C, T> {
+    /// Stops the transfer
+    fn stop(&mut self) {
+        self.channel.disable();
+        self.target.disable_dma();
+
+        // ensure the DMA's writes to the buffer are visible before it is handed back
+        atomic::compiler_fence(Ordering::Acquire);
+    }
+}
+
+/// A running DMA transfer
+///
+/// The buffer, the channel and the peripheral are released with `wait` or `stop`.
+/// Dropping a running transfer stops it.
+pub struct Transfer<B, C: Channel, T: Target> {
+    // always `Some` outside of `wait` and `drop`
+    inner: Option<TransferInner<B, C, T>>,
+}
+
+impl<W: Word, C: Channel, T: Target> Transfer<&'static mut [W], C, T> {
+    /// Starts a transfer from the peripheral `target` into `buffer`
+    ///
+    /// The peripheral address of `channel` has to be set up beforehand.
+    pub fn start_read(buffer: &'static mut [W], mut channel: C, target: T) -> Self {
+        let (ptr, len) = (buffer.as_mut_ptr(), buffer.len());
+        assert!(len <= usize::from(u16::max_value()));
+
+        // NOTE(unsafe) the transfer takes ownership of the 'static buffer
+        unsafe { channel.set_memory_address(ptr as u32, Increment::Enable) };
+        channel.set_transfer_length(len as u16);
+        channel.set_word_size(W::size());
+        channel.set_direction(Direction::FromPeripheral);
+
+        Self::start(buffer, channel, target)
+    }
+}
+
+impl<W: Word, C: Channel, T: Target> Transfer<&'static [W], C, T> {
+    /// Starts a transfer from `buffer` to the peripheral `target`
+    ///
+    /// The peripheral address of `channel` has to be set up beforehand.
+    pub fn start_write(buffer: &'static [W], mut channel: C, target: T) -> Self {
+        let (ptr, len) = (buffer.as_ptr(), buffer.len());
+        assert!(len <= usize::from(u16::max_value()));
+
+        // NOTE(unsafe) the transfer keeps the 'static buffer borrowed
+        unsafe { channel.set_memory_address(ptr as u32, Increment::Enable) };
+        channel.set_transfer_length(len as u16);
+        channel.set_word_size(W::size());
+        channel.set_direction(Direction::FromMemory);
+
+        Self::start(buffer, channel, target)
+    }
+}
+
+impl<B, C: Channel, T: Target> Transfer<B, C, T> {
+    fn start(buffer: B, mut channel: C, mut target: T) -> Self {
+        channel.clear_event(Event::Any);
+
+        // ensure all writes to the buffer are done before the DMA takes over
+        atomic::compiler_fence(Ordering::Release);
+
+        channel.enable();
+        target.enable_dma();
+
+        Self {
+            inner: Some(TransferInner {
+                buffer,
+                channel,
+                target,
+            }),
+        }
+    }
+
+    /// Returns `true` if the transfer is done
+    ///
+    /// Returns an error, if a bus error or an error of the peripheral aborted the
+    /// transfer. The resources are then released with `stop`.
+    pub fn is_complete(&mut self) -> Result<bool, Error<T::Error>> {
+        let inner = self.inner.as_mut().unwrap();
+        if inner.channel.event_occurred(Event::TransferError) {
+            return Err(Error::Transfer);
+        }
+        inner.target.check_error().map_err(Error::Target)?;
+
+        Ok(inner.channel.event_occurred(Event::TransferComplete))
+    }
+
+    /// Blocks until the transfer is done and releases the resources
+    ///
+    /// If the transfer is aborted by an error, the resources are released along with
+    /// the error.
+    pub fn wait(mut self) -> Result<(B, C, T), TransferError<B, C, T>> {
+        loop {
+            match self.is_complete() {
+                Ok(true) => return Ok(self.stop()),
+                Ok(false) => {}
+                Err(error) => return Err((error, self.stop())),
+            }
+        }
+    }
+
+    /// Stops the transfer, whether it is done or not, and releases the resources
+    pub fn stop(mut self) -> (B, C, T) {
+        let mut inner = self.inner.take().unwrap();
+        inner.stop();
+
+        (inner.buffer, inner.channel, inner.target)
+    }
+}
+
+impl<B, C: Channel, T: Target> Drop for Transfer<B, C, T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.stop();
+        }
+    }
+}
+
+/// Half of a circular double buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Half {
+    /// The first half
+    First,
+    /// The second half
+    Second,
+}
+
+/// A DMA transfer running in circular mode over a double buffer
+///
+/// While the DMA works on one half of the buffer, the other half can be accessed with
+/// `peek`. The resources are released with `stop`. Dropping a running transfer stops it.
+pub struct CircBuffer<W: 'static, C: Channel, T: Target> {
+    // always `Some` outside of `stop` and `drop`
+    inner: Option<TransferInner<&'static mut [W], C, T>>,
+}
+
+impl<W: Word, C: Channel, T: Target> CircBuffer<W, C, T> {
+    /// Starts continuously filling `buffer` from the peripheral `target`
+    ///
+    /// The peripheral address of `channel` has to be set up beforehand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `buffer` is odd.
+    pub fn start_read(buffer: &'static mut [W], mut channel: C, target: T) -> Self {
+        channel.set_direction(Direction::FromPeripheral);
+        Self::start(buffer, channel, target)
+    }
+
+    /// Starts continuously sending `buffer` to the peripheral `target`
+    ///
+    /// The peripheral address of `channel` has to be set up beforehand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `buffer` is odd.
+    pub fn start_write(buffer: &'static mut [W], mut channel: C, target: T) -> Self {
+        channel.set_direction(Direction::FromMemory);
+        Self::start(buffer, channel, target)
+    }
+
+    fn start(buffer: &'static mut [W], mut channel: C, mut target: T) -> Self {
+        let (ptr, len) = (buffer.as_mut_ptr(), buffer.len());
+        assert!(len % 2 == 0 && len <= usize::from(u16::max_value()));
+
+        // NOTE(unsafe) the circular buffer takes ownership of the 'static buffer
+        unsafe { channel.set_memory_address(ptr as u32, Increment::Enable) };
+        channel.set_transfer_length(len as u16);
+        channel.set_word_size(W::size());
+        channel.set_circular(true);
+        channel.clear_event(Event::Any);
+
+        // ensure all writes to the buffer are done before the DMA takes over
+        atomic::compiler_fence(Ordering::Release);
+
+        channel.enable();
+        target.enable_dma();
+
+        CircBuffer {
+            inner: Some(TransferInner {
+                buffer,
+                channel,
+                target,
+            }),
+        }
+    }
+
+    /// Returns the half, which was completed by the DMA since the last call, if any
+    fn completed_half(&mut self) -> nb::Result<Half, Error<T::Error>> {
+        let inner = self.inner.as_mut().unwrap();
+        if inner.channel.event_occurred(Event::TransferError) {
+            return Err(nb::Error::Other(Error::Transfer));
+        }
+        inner
+            .target
+            .check_error()
+            .map_err(|e| nb::Error::Other(Error::Target(e)))?;
+
+        let channel = &mut inner.channel;
+        let half = channel.event_occurred(Event::HalfTransfer);
+        let complete = channel.event_occurred(Event::TransferComplete);
+
+        match (half, complete) {
+            (true, true) => {
+                channel.clear_event(Event::HalfTransfer);
+                channel.clear_event(Event::TransferComplete);
+                Err(nb::Error::Other(Error::Overrun))
+            }
+            (true, false) => {
+                channel.clear_event(Event::HalfTransfer);
+                Ok(Half::First)
+            }
+            (false, true) => {
+                channel.clear_event(Event::TransferComplete);
+                Ok(Half::Second)
+            }
+            (false, false) => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Calls `f` with the half of the buffer, which was just completed by the DMA
+    ///
+    /// When reading, the half holds the latest data. When writing, it should be refilled
+    /// before the DMA wraps around to it. Returns `WouldBlock` if no half was completed
+    /// since the last call.
+    ///
+    /// Returns an error, if a bus error or an error of the peripheral stopped the
+    /// transfer. It goes on after `restart`.
+    pub fn peek<R, F>(&mut self, f: F) -> nb::Result<R, Error<T::Error>>
+    where
+        F: FnOnce(&mut [W], Half) -> R,
+    {
+        let half = self.completed_half()?;
+
+        let buffer = &mut self.inner.as_mut().unwrap().buffer;
+        let middle = buffer.len() / 2;
+        let (first, second) = buffer.split_at_mut(middle);
+        let slice = match half {
+            Half::First => first,
+            Half::Second => second,
+        };
+
+        atomic::compiler_fence(Ordering::Acquire);
+        let result = f(slice, half);
+        atomic::compiler_fence(Ordering::Release);
+
+        Ok(result)
+    }
+
+    /// Returns a shared reference to the channel, e.g. to check its events
+    pub fn channel(&self) -> &C {
+        &self.inner.as_ref().unwrap().channel
+    }
+
+    /// Returns a mutable reference to the channel, e.g. to listen for its events
+    pub fn channel_mut(&mut self) -> &mut C {
+        &mut self.inner.as_mut().unwrap().channel
+    }
+
+    /// Returns a shared reference to the peripheral, e.g. to check its events
+    pub fn target(&self) -> &T {
+        &self.inner.as_ref().unwrap().target
+    }
+
+    /// Returns a mutable reference to the peripheral, e.g. to clear its events
+    pub fn target_mut(&mut self) -> &mut T {
+        &mut self.inner.as_mut().unwrap().target
+    }
+
+    /// Restarts the transfer at the beginning of the buffer
+    ///
+    /// The DMA requests of the peripheral are disabled and enabled again, which recovers
+    /// from an error of the peripheral, e.g. an ADC overrun.
+    pub fn restart(&mut self) {
+        let inner = self.inner.as_mut().unwrap();
+        inner.stop();
+
+        // the transfer length can only be written while the channel is disabled
+        inner.channel.set_transfer_length(inner.buffer.len() as u16);
+        inner.channel.clear_event(Event::Any);
+
+        atomic::compiler_fence(Ordering::Release);
+
+        inner.channel.enable();
+        inner.target.enable_dma();
+    }
+
+    /// Stops the transfer and releases the resources
+    pub fn stop(mut self) -> (&'static mut [W], C, T) {
+        let mut inner = self.inner.take().unwrap();
+        inner.stop();
+        inner.channel.set_circular(false);
+
+        (inner.buffer, inner.channel, inner.target)
+    }
+}
+
+impl<W: 'static, C: Channel, T: Target> Drop for CircBuffer<W, C, T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.stop();
+            inner.channel.set_circular(false);
+        }
+    }
+}
+
+macro_rules! dma {
+    (
+        $DMAx:ident, $dmax:ident, $dmaxen:ident,
+        channels: {
+            $($Ci:ident: ($chi:ident, $i:expr, $ccri:ident, $cndtri:ident, $cpari:ident, $cmari:ident),)+
+        },
+    ) => {
+        /// Direct memory access controller
+        pub mod $dmax {
+            use super::{private, Channel, Direction, Event, Increment, Priority, WordSize};
+            use crate::rcc::AHB;
+            use crate::stm32::$DMAx;
+
+            /// DMA channels
+            pub struct Channels {
+                $(
+                    /// DMA channel
+                    pub $chi: $Ci,
+                )+
+            }
+
+            $(
+                /// DMA channel
+                pub struct $Ci {
+                    _0: (),
+                }
+
+                impl $Ci {
+                    fn isr_mask(event: Event) -> u32 {
+                        1 << (($i - 1) * 4 + event.offset())
+                    }
+                }
+
+                impl private::Sealed for $Ci {}
+
+                impl Channel for $Ci {
+                    unsafe fn set_peripheral_address(&mut self, pa: u32, inc: Increment) {
+                        let dma = &*$DMAx::ptr();
+                        dma.$cpari.write(|w| w.bits(pa));
+                        dma.$ccri.modify(|_, w| w.pinc().bit(inc == Increment::Enable));
+                    }
+
+                    unsafe fn set_memory_address(&mut self, ma: u32, inc: Increment) {
+                        let dma = &*$DMAx::ptr();
+                        dma.$cmari.write(|w| w.bits(ma));
+                        dma.$ccri.modify(|_, w| w.minc().bit(inc == Increment::Enable));
+                    }
+
+                    fn set_transfer_length(&mut self, len: u16) {
+                        // NOTE(unsafe) this channel owns its registers
+                        unsafe {
+                            (*$DMAx::ptr()).$cndtri.write(|w| w.bits(u32::from(len)));
+                        }
+                    }
+
+                    fn remaining_transfers(&self) -> u16 {
+                        // NOTE(unsafe) atomic read with no side effects
+                        unsafe { (*$DMAx::ptr()).$cndtri.read().bits() as u16 }
+                    }
+
+                    fn set_word_size(&mut self, size: WordSize) {
+                        // NOTE(unsafe) this channel owns its registers, and only valid
+                        // values are written
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| {
+                                w.psize().bits(size.bitcode()).msize().bits(size.bitcode())
+                            });
+                        }
+                    }
+
+                    fn set_priority_level(&mut self, priority: Priority) {
+                        // NOTE(unsafe) this channel owns its registers, and only valid
+                        // values are written
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| w.pl().bits(priority.bitcode()));
+                        }
+                    }
+
+                    fn set_direction(&mut self, direction: Direction) {
+                        // NOTE(unsafe) this channel owns its registers
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| {
+                                w.dir().bit(direction == Direction::FromMemory)
+                            });
+                        }
+                    }
+
+                    fn set_circular(&mut self, circular: bool) {
+                        // NOTE(unsafe) this channel owns its registers
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| w.circ().bit(circular));
+                        }
+                    }
+
+                    fn listen(&mut self, event: Event) {
+                        // NOTE(unsafe) this channel owns its registers
+                        let ccr = unsafe { &(*$DMAx::ptr()).$ccri };
+                        match event {
+                            Event::HalfTransfer => ccr.modify(|_, w| w.htie().set_bit()),
+                            Event::TransferComplete => ccr.modify(|_, w| w.tcie().set_bit()),
+                            Event::TransferError => ccr.modify(|_, w| w.teie().set_bit()),
+                            Event::Any => ccr.modify(|_, w| {
+                                w.htie().set_bit().tcie().set_bit().teie().set_bit()
+                            }),
+                        }
+                    }
+
+                    fn unlisten(&mut self, event: Event) {
+                        // NOTE(unsafe) this channel owns its registers
+                        let ccr = unsafe { &(*$DMAx::ptr()).$ccri };
+                        match event {
+                            Event::HalfTransfer => ccr.modify(|_, w| w.htie().clear_bit()),
+                            Event::TransferComplete => ccr.modify(|_, w| w.tcie().clear_bit()),
+                            Event::TransferError => ccr.modify(|_, w| w.teie().clear_bit()),
+                            Event::Any => ccr.modify(|_, w| {
+                                w.htie().clear_bit().tcie().clear_bit().teie().clear_bit()
+                            }),
+                        }
+                    }
+
+                    fn event_occurred(&self, event: Event) -> bool {
+                        // NOTE(unsafe) atomic read with no side effects
+                        let isr = unsafe { (*$DMAx::ptr()).isr.read().bits() };
+                        isr & Self::isr_mask(event) != 0
+                    }
+
+                    fn clear_event(&mut self, event: Event) {
+                        // NOTE(unsafe) atomic write to a stateless register, which only
+                        // affects the flags of this channel
+                        unsafe {
+                            (*$DMAx::ptr()).ifcr.write(|w| w.bits(Self::isr_mask(event)));
+                        }
+                    }
+
+                    fn enable(&mut self) {
+                        // NOTE(unsafe) this channel owns its registers
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| w.en().set_bit());
+                        }
+                    }
+
+                    fn disable(&mut self) {
+                        // NOTE(unsafe) this channel owns its registers
+                        unsafe {
+                            (*$DMAx::ptr()).$ccri.modify(|_, w| w.en().clear_bit());
+                        }
+                    }
+
+                    fn is_enabled(&self) -> bool {
+                        // NOTE(unsafe) atomic read with no side effects
+                        unsafe { (*$DMAx::ptr()).$ccri.read().en().bit_is_set() }
+                    }
+                }
+            )+
+
+            impl super::DmaExt for $DMAx {
+                type Channels = Channels;
+
+                fn split(self, ahb: &mut AHB) -> Channels {
+                    ahb.enr().modify(|_, w| w.$dmaxen().set_bit());
+
+                    // reset the channels, as the DMA has no reset bit in AHBRSTR
+                    $(
+                        self.$ccri.reset();
+                    )+
+
+                    Channels {
+                        $($chi: $Ci { _0: () },)+
+                    }
+                }
+            }
+        }
+    };
+}
+
+dma! {
+    DMA1, dma1, dma1en,
+    channels: {
+        C1: (ch1, 1, ccr1, cndtr1, cpar1, cmar1),
+        C2: (ch2, 2, ccr2, cndtr2, cpar2, cmar2),
+        C3: (ch3, 3, ccr3, cndtr3, cpar3, cmar3),
+        C4: (ch4, 4, ccr4, cndtr4, cpar4, cmar4),
+        C5: (ch5, 5, ccr5, cndtr5, cpar5, cmar5),
+        C6: (ch6, 6, ccr6, cndtr6, cpar6, cmar6),
+        C7: (ch7, 7, ccr7, cndtr7, cpar7, cmar7),
+    },
+}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398",
+))]
+dma! {
+    DMA2, dma2, dma2en,
+    channels: {
+        C1: (ch1, 1, ccr1, cndtr1, cpar1, cmar1),
+        C2: (ch2, 2, ccr2, cndtr2, cpar2, cmar2),
+        C3: (ch3, 3, ccr3, cndtr3, cpar3, cmar3),
+        C4: (ch4, 4, ccr4, cndtr4, cpar4, cmar4),
+        C5: (ch5, 5, ccr5, cndtr5, cpar5, cmar5),
+    },
+}
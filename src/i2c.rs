@@ -1,26 +1,70 @@
 //! Inter-Integrated Circuit (I2C) bus
+//!
+//! `I2c` implements the blocking embedded-hal traits. For non-blocking transfers, it can
+//! either be turned into an interrupt driven `I2cInterrupt`, or move data with the DMA
+//! using `I2c::write_dma` and `I2c::read_dma`.
+
+use core::mem;
 
 use crate::stm32::{I2C1, I2C2};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+use crate::stm32::I2C3;
 use cast::u8;
 
-use crate::gpio::gpioa::{PA10, PA9};
+use crate::dma::{self, dma1};
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+use crate::gpio::gpioa::PA8;
+use crate::gpio::gpioa::{PA10, PA14, PA15, PA9};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+use crate::gpio::gpiob::PB5;
 use crate::gpio::gpiob::{PB6, PB7, PB8, PB9};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+use crate::gpio::gpioc::PC9;
 #[cfg(any(
     feature = "stm32f302",
-    feature = "stm32f334",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
-    feature = "stm32f318",
-    feature = "stm32f328",
     feature = "stm32f358",
-    feature = "stm32f398",
     feature = "stm32f373",
-    feature = "stm32f378"
+    feature = "stm32f378",
+    feature = "stm32f398"
 ))]
 use crate::gpio::gpiof::PF6;
 use crate::gpio::gpiof::{PF0, PF1};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+use crate::gpio::{AF3, AF8};
 use crate::gpio::AF4;
 use crate::hal::blocking::i2c::{Write, WriteRead};
 use crate::rcc::{Clocks, APB1};
@@ -33,6 +77,8 @@ pub enum Error {
     Bus,
     /// Arbitration loss
     Arbitration,
+    /// Acknowledge failure, the slave did not acknowledge its address or a byte
+    Nack,
     // Overrun, // slave mode only
     // Pec, // SMBUS mode only
     // Timeout, // SMBUS mode only
@@ -48,7 +94,7 @@ pub unsafe trait SclPin<I2C> {}
 /// SDA pin -- DO NOT IMPLEMENT THIS TRAIT
 pub unsafe trait SdaPin<I2C> {}
 
-// unsafe impl SclPin<I2C1> for PA15<AF4> {}
+unsafe impl SclPin<I2C1> for PA15<AF4> {}
 unsafe impl SclPin<I2C1> for PB6<AF4> {}
 unsafe impl SclPin<I2C1> for PB8<AF4> {}
 
@@ -56,33 +102,150 @@ unsafe impl SclPin<I2C2> for PA9<AF4> {}
 unsafe impl SclPin<I2C2> for PF1<AF4> {}
 #[cfg(any(
     feature = "stm32f302",
-    feature = "stm32f334",
     feature = "stm32f303xb",
     feature = "stm32f303xc",
     feature = "stm32f303xd",
     feature = "stm32f303xe",
-    feature = "stm32f318",
-    feature = "stm32f328",
     feature = "stm32f358",
-    feature = "stm32f398",
     feature = "stm32f373",
-    feature = "stm32f378"
+    feature = "stm32f378",
+    feature = "stm32f398"
 ))]
 unsafe impl SclPin<I2C2> for PF6<AF4> {}
 
-// unsafe impl SdaPin<I2C1> for PA14<AF4> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+unsafe impl SclPin<I2C3> for PA8<AF3> {}
+
+unsafe impl SdaPin<I2C1> for PA14<AF4> {}
 unsafe impl SdaPin<I2C1> for PB7<AF4> {}
 unsafe impl SdaPin<I2C1> for PB9<AF4> {}
 
 unsafe impl SdaPin<I2C2> for PA10<AF4> {}
 unsafe impl SdaPin<I2C2> for PF0<AF4> {}
 
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+unsafe impl SdaPin<I2C3> for PB5<AF8> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+unsafe impl SdaPin<I2C3> for PC9<AF3> {}
+
+unsafe impl dma::TxChannel<I2C1> for dma1::C6 {}
+unsafe impl dma::RxChannel<I2C1> for dma1::C7 {}
+
+#[cfg(not(feature = "stm32f334"))]
+unsafe impl dma::TxChannel<I2C2> for dma1::C4 {}
+#[cfg(not(feature = "stm32f334"))]
+unsafe impl dma::RxChannel<I2C2> for dma1::C5 {}
+
 /// I2C peripheral operating in master mode
 pub struct I2c<I2C, PINS> {
     i2c: I2C,
     pins: PINS,
 }
 
+/// I2C peripheral operating in interrupt driven master mode
+///
+/// A transfer is started with `start_write`, `start_read` or `start_write_read` and makes
+/// progress in `on_interrupt`, which has to be called from both the event and the error
+/// interrupt handler of the peripheral (e.g. `I2C1_EV_EXTI23` and `I2C1_ER`).
+/// Its outcome is collected with `poll`.
+pub struct I2cInterrupt<I2C, PINS> {
+    i2c: I2C,
+    pins: PINS,
+    addr: u8,
+    state: State,
+}
+
+/// Outcome of a non-blocking transfer
+pub struct Completed {
+    /// Result of the transfer
+    pub result: Result<(), Error>,
+    /// The receive buffer handed to `start_read` or `start_write_read`
+    ///
+    /// It is handed back on errors as well.
+    pub buffer: Option<&'static mut [u8]>,
+}
+
+/// State of a non-blocking transfer
+enum State {
+    /// No transfer is running and there is no outcome to collect
+    Idle,
+    /// Sending `bytes`, followed by a repeated START to receive into `read`, if any
+    Write {
+        bytes: &'static [u8],
+        sent: usize,
+        read: Option<&'static mut [u8]>,
+    },
+    /// Receiving into `buffer`
+    Read {
+        buffer: &'static mut [u8],
+        received: usize,
+    },
+    /// The transfer is finished, but its outcome was not collected yet
+    Done(Completed),
+}
+
+impl<I2C, PINS> I2cInterrupt<I2C, PINS> {
+    /// Returns `true` if the last transfer is finished and its outcome can be collected
+    pub fn is_done(&self) -> bool {
+        match self.state {
+            State::Done(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if a transfer is running
+    pub fn is_busy(&self) -> bool {
+        match self.state {
+            State::Write { .. } | State::Read { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Collects the outcome of the last transfer
+    ///
+    /// Returns `None` while the transfer is still running or if no transfer was started.
+    pub fn poll(&mut self) -> Option<Completed> {
+        match mem::replace(&mut self.state, State::Idle) {
+            State::Done(completed) => Some(completed),
+            state => {
+                self.state = state;
+                None
+            }
+        }
+    }
+
+    fn finish(&mut self, result: Result<(), Error>) {
+        let buffer = match mem::replace(&mut self.state, State::Idle) {
+            State::Write { read, .. } => read,
+            State::Read { buffer, .. } => Some(buffer),
+            state => {
+                // nothing running, e.g. the STOP after an error
+                self.state = state;
+                return;
+            }
+        };
+
+        self.state = State::Done(Completed { result, buffer });
+    }
+}
+
 macro_rules! busy_wait {
     ($i2c:expr, $flag:ident, $variant:ident) => {
         loop {
@@ -102,7 +265,7 @@ macro_rules! busy_wait {
 }
 
 macro_rules! hal {
-    ($($I2CX:ident: ($i2cX:ident, $i2cXen:ident, $i2cXrst:ident),)+) => {
+    ($($I2CX:ident: ($i2cX:ident, $i2cXen:ident, $i2cXrst:ident, $i2cXclk:ident),)+) => {
         $(
             impl<SCL, SDA> I2c<$I2CX, (SCL, SDA)> {
                 /// Configures the I2C peripheral to work in master mode
@@ -126,14 +289,14 @@ macro_rules! hal {
                     assert!(freq <= 1_000_000);
 
                     // TODO review compliance with the timing requirements of I2C
-                    // t_I2CCLK = 1 / PCLK1
+                    // t_I2CCLK = 1 / I2CCLK (HSI or SYSCLK, see `rcc::I2cClock`)
                     // t_PRESC  = (PRESC + 1) * t_I2CCLK
                     // t_SCLL   = (SCLL + 1) * t_PRESC
                     // t_SCLH   = (SCLH + 1) * t_PRESC
                     //
                     // t_SYNC1 + t_SYNC2 > 4 * t_I2CCLK
                     // t_SCL ~= t_SYNC1 + t_SYNC2 + t_SCLL + t_SCLH
-                    let i2cclk = clocks.pclk1().0;
+                    let i2cclk = clocks.$i2cXclk().0;
                     let ratio = i2cclk / freq - 4;
                     let (presc, scll, sclh, sdadel, scldel) = if freq >= 100_000 {
                         // fast-mode or fast-mode plus
@@ -207,6 +370,336 @@ macro_rules! hal {
                 }
             }
 
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Turns the I2C into an interrupt driven `I2cInterrupt`
+                pub fn into_interrupt(self) -> I2cInterrupt<$I2CX, PINS> {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.txie()
+                            .set_bit()
+                            .rxie()
+                            .set_bit()
+                            .tcie()
+                            .set_bit()
+                            .stopie()
+                            .set_bit()
+                            .nackie()
+                            .set_bit()
+                            .errie()
+                            .set_bit()
+                    });
+
+                    I2cInterrupt {
+                        i2c: self.i2c,
+                        pins: self.pins,
+                        addr: 0,
+                        state: State::Idle,
+                    }
+                }
+
+                /// Writes `bytes` to the slave with address `addr` using the DMA
+                ///
+                /// The transfer completes, once the DMA moved the last byte, the STOP
+                /// condition is generated automatically afterwards. A NACK of the slave,
+                /// a bus error or an arbitration loss abort the transfer with
+                /// `dma::Error::Target`.
+                pub fn write_dma<C>(
+                    self,
+                    addr: u8,
+                    bytes: &'static [u8],
+                    mut channel: C,
+                ) -> dma::Transfer<&'static [u8], C, Self>
+                where
+                    C: dma::TxChannel<$I2CX>,
+                {
+                    // TODO support transfers of more than 255 bytes
+                    assert!(bytes.len() < 256 && bytes.len() > 0);
+
+                    // NOTE(unsafe) TXDR is the 8-bit transmit data register
+                    unsafe {
+                        channel.set_peripheral_address(
+                            &self.i2c.txdr as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+
+                    // START and prepare to send `bytes`, the clock is stretched until the
+                    // DMA writes the first byte
+                    self.i2c.cr2.write(|w| {
+                        w.sadd()
+                            .bits(u16::from(addr << 1))
+                            .rd_wrn()
+                            .write()
+                            .nbytes()
+                            .bits(bytes.len() as u8)
+                            .start()
+                            .start()
+                            .autoend()
+                            .automatic()
+                    });
+
+                    dma::Transfer::start_write(bytes, channel, self)
+                }
+
+                /// Reads from the slave with address `addr` into `buffer` using the DMA
+                ///
+                /// See `write_dma` for the completion semantics.
+                pub fn read_dma<C>(
+                    self,
+                    addr: u8,
+                    buffer: &'static mut [u8],
+                    mut channel: C,
+                ) -> dma::Transfer<&'static mut [u8], C, Self>
+                where
+                    C: dma::RxChannel<$I2CX>,
+                {
+                    // TODO support transfers of more than 255 bytes
+                    assert!(buffer.len() < 256 && buffer.len() > 0);
+
+                    // NOTE(unsafe) RXDR is the 8-bit receive data register
+                    unsafe {
+                        channel.set_peripheral_address(
+                            &self.i2c.rxdr as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+
+                    let len = buffer.len() as u8;
+                    let transfer = dma::Transfer::start_read(buffer, channel, self);
+
+                    // NOTE(unsafe) the transfer owns the peripheral, START is issued only
+                    // after the DMA is ready to receive
+                    unsafe {
+                        (*$I2CX::ptr()).cr2.write(|w| {
+                            w.sadd()
+                                .bits(u16::from(addr << 1))
+                                .rd_wrn()
+                                .read()
+                                .nbytes()
+                                .bits(len)
+                                .start()
+                                .start()
+                                .autoend()
+                                .automatic()
+                        });
+                    }
+
+                    transfer
+                }
+            }
+
+            impl<PINS> dma::Target for I2c<$I2CX, PINS> {
+                type Error = Error;
+
+                fn enable_dma(&mut self) {
+                    self.i2c.cr1.modify(|_, w| w.txdmaen().set_bit().rxdmaen().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.i2c.cr1.modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+                }
+
+                fn check_error(&mut self) -> Result<(), Error> {
+                    let isr = self.i2c.isr.read();
+
+                    if isr.berr().is_error() {
+                        self.i2c.icr.write(|w| w.berrcf().set_bit());
+                        Err(Error::Bus)
+                    } else if isr.arlo().is_lost() {
+                        self.i2c.icr.write(|w| w.arlocf().set_bit());
+                        Err(Error::Arbitration)
+                    } else if isr.nackf().bit_is_set() {
+                        // a STOP condition is generated automatically after a NACK
+                        self.i2c.icr.write(|w| w.nackcf().set_bit());
+                        Err(Error::Nack)
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+
+            impl<PINS> I2cInterrupt<$I2CX, PINS> {
+                /// Starts writing `bytes` to the slave with address `addr`
+                ///
+                /// # Panics
+                ///
+                /// Panics if a transfer is running or its outcome was not collected yet.
+                pub fn start_write(&mut self, addr: u8, bytes: &'static [u8]) {
+                    // TODO support transfers of more than 255 bytes
+                    assert!(bytes.len() < 256 && bytes.len() > 0);
+                    self.start(addr, bytes, None);
+                }
+
+                /// Starts writing `bytes` to the slave with address `addr`, followed by
+                /// a repeated START to read into `buffer`
+                ///
+                /// # Panics
+                ///
+                /// Panics if a transfer is running or its outcome was not collected yet.
+                pub fn start_write_read(
+                    &mut self,
+                    addr: u8,
+                    bytes: &'static [u8],
+                    buffer: &'static mut [u8],
+                ) {
+                    // TODO support transfers of more than 255 bytes
+                    assert!(bytes.len() < 256 && bytes.len() > 0);
+                    assert!(buffer.len() < 256 && buffer.len() > 0);
+                    self.start(addr, bytes, Some(buffer));
+                }
+
+                /// Starts reading from the slave with address `addr` into `buffer`
+                ///
+                /// # Panics
+                ///
+                /// Panics if a transfer is running or its outcome was not collected yet.
+                pub fn start_read(&mut self, addr: u8, buffer: &'static mut [u8]) {
+                    // TODO support transfers of more than 255 bytes
+                    assert!(buffer.len() < 256 && buffer.len() > 0);
+                    self.assert_idle();
+
+                    self.addr = addr;
+                    self.start_read_phase(buffer);
+                }
+
+                fn start(
+                    &mut self,
+                    addr: u8,
+                    bytes: &'static [u8],
+                    read: Option<&'static mut [u8]>,
+                ) {
+                    self.assert_idle();
+
+                    // with a read phase following, the STOP is replaced by a repeated START
+                    // once the transfer complete (TC) flag is set
+                    let autoend = read.is_none();
+                    self.addr = addr;
+                    self.state = State::Write {
+                        bytes,
+                        sent: 0,
+                        read,
+                    };
+
+                    self.i2c.cr2.write(|w| {
+                        w.sadd()
+                            .bits(u16::from(addr << 1))
+                            .rd_wrn()
+                            .write()
+                            .nbytes()
+                            .bits(bytes.len() as u8)
+                            .start()
+                            .start()
+                            .autoend()
+                            .bit(autoend)
+                    });
+                }
+
+                fn start_read_phase(&mut self, buffer: &'static mut [u8]) {
+                    let len = buffer.len() as u8;
+                    self.state = State::Read {
+                        buffer,
+                        received: 0,
+                    };
+
+                    self.i2c.cr2.write(|w| {
+                        w.sadd()
+                            .bits(u16::from(self.addr << 1))
+                            .rd_wrn()
+                            .read()
+                            .nbytes()
+                            .bits(len)
+                            .start()
+                            .start()
+                            .autoend()
+                            .automatic()
+                    });
+                }
+
+                fn assert_idle(&self) {
+                    match self.state {
+                        State::Idle => {}
+                        _ => panic!("I2C transfer in progress"),
+                    }
+                }
+
+                /// Advances the running transfer
+                ///
+                /// Call this from both the event and the error interrupt handler.
+                pub fn on_interrupt(&mut self) {
+                    let isr = self.i2c.isr.read();
+
+                    let error = if isr.berr().is_error() {
+                        self.i2c.icr.write(|w| w.berrcf().set_bit());
+                        Some(Error::Bus)
+                    } else if isr.arlo().is_lost() {
+                        self.i2c.icr.write(|w| w.arlocf().set_bit());
+                        Some(Error::Arbitration)
+                    } else if isr.nackf().bit_is_set() {
+                        // a STOP condition is generated automatically after a NACK
+                        self.i2c.icr.write(|w| w.nackcf().set_bit());
+                        Some(Error::Nack)
+                    } else {
+                        None
+                    };
+
+                    if let Some(error) = error {
+                        self.finish(Err(error));
+                    }
+
+                    let mut read_phase = None;
+                    match &mut self.state {
+                        State::Write { bytes, sent, read } => {
+                            if isr.txis().is_empty() && *sent < bytes.len() {
+                                self.i2c.txdr.write(|w| w.txdata().bits(bytes[*sent]));
+                                *sent += 1;
+                            } else if isr.tc().is_complete() {
+                                read_phase = read.take();
+                            }
+                        }
+                        State::Read { buffer, received } => {
+                            if isr.rxne().is_not_empty() && *received < buffer.len() {
+                                buffer[*received] = self.i2c.rxdr.read().rxdata().bits();
+                                *received += 1;
+                            }
+                        }
+                        State::Idle | State::Done(_) => {}
+                    }
+
+                    if let Some(buffer) = read_phase {
+                        self.start_read_phase(buffer);
+                    }
+
+                    if isr.stopf().bit_is_set() {
+                        self.i2c.icr.write(|w| w.stopcf().set_bit());
+                        self.finish(Ok(()));
+                    }
+                }
+
+                /// Turns the I2C back into a blocking `I2c`
+                ///
+                /// Should only be called while no transfer is running.
+                pub fn into_blocking(self) -> I2c<$I2CX, PINS> {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.txie()
+                            .clear_bit()
+                            .rxie()
+                            .clear_bit()
+                            .tcie()
+                            .clear_bit()
+                            .stopie()
+                            .clear_bit()
+                            .nackie()
+                            .clear_bit()
+                            .errie()
+                            .clear_bit()
+                    });
+
+                    I2c {
+                        i2c: self.i2c,
+                        pins: self.pins,
+                    }
+                }
+            }
+
             impl<PINS> Write for I2c<$I2CX, PINS> {
                 type Error = Error;
 
@@ -330,11 +823,22 @@ macro_rules! hal {
     feature = "stm32f398",
 ))]
 hal! {
-    I2C1: (i2c1, i2c1en, i2c1rst),
-    I2C2: (i2c2, i2c2en, i2c2rst),
+    I2C1: (i2c1, i2c1en, i2c1rst, i2c1clk),
+    I2C2: (i2c2, i2c2en, i2c2rst, i2c2clk),
 }
 
 #[cfg(feature = "stm32f334")]
 hal! {
-    I2C1: (i2c1, i2c1en, i2c1rst),
+    I2C1: (i2c1, i2c1en, i2c1rst, i2c1clk),
+}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f398",
+))]
+hal! {
+    I2C3: (i2c3, i2c3en, i2c3rst, i2c3clk),
 }
@@ -0,0 +1,661 @@
+//! Inter-IC Sound (I2S) bus
+//!
+//! SPI2 and SPI3 can operate as I2S interfaces. Each of them is paired with an extension
+//! block (I2S2ext, I2S3ext), which drives a second data line in the opposite direction for
+//! full duplex operation. An extension block is always a slave of the clock and word select
+//! signals of its main interface.
+//!
+//! Data is exchanged as 16-bit half-words. Samples of 24 or 32 bits take two half-words,
+//! the most significant one first. `write_frame` and `read_frame` take care of this.
+//!
+//! Both the interface and its extension block start disabled. They are enabled with
+//! `enable` or by starting a circular DMA transfer, the extension block first. Stopping
+//! the transfer disables them again.
+
+use core::ptr;
+
+use crate::dma::{self, dma1, dma2, CircBuffer};
+use crate::stm32::{I2S2EXT, I2S3EXT, SPI2, SPI3};
+use nb;
+use void::Void;
+
+use crate::gpio::gpioa::{PA15, PA4, PA8};
+use crate::gpio::gpiob::{PB12, PB13, PB14, PB15, PB3, PB4, PB5};
+use crate::gpio::gpioc::{PC10, PC11, PC12, PC6, PC7};
+use crate::gpio::{AF5, AF6};
+pub use crate::hal::spi::Polarity;
+use crate::rcc::{Clocks, APB1};
+use crate::time::Hertz;
+
+/// I2S error
+#[derive(Debug)]
+pub enum Error {
+    /// Overrun occurred
+    Overrun,
+    /// Underrun occurred
+    Underrun,
+    /// The word select signal changed unexpectedly (slave modes only)
+    Frame,
+    #[doc(hidden)]
+    _Extensible,
+}
+
+// FIXME these should be "closed" traits
+/// Serial clock (CK) pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait CkPin<SPI> {}
+
+/// Word select (WS) pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait WsPin<SPI> {}
+
+/// Serial data (SD) pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait SdPin<SPI> {}
+
+/// Serial data pin of the extension block (I2Sx_ext_SD) -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait ExtSdPin<SPI> {}
+
+/// Master clock (MCK) output pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait MckPin<SPI> {
+    #[doc(hidden)]
+    const OUTPUT: bool = true;
+}
+
+/// Placeholder for the MCK pin, if the master clock is not output
+pub struct NoMck;
+
+unsafe impl<SPI> MckPin<SPI> for NoMck {
+    const OUTPUT: bool = false;
+}
+
+unsafe impl CkPin<SPI2> for PB13<AF5> {}
+
+unsafe impl CkPin<SPI3> for PB3<AF6> {}
+unsafe impl CkPin<SPI3> for PC10<AF6> {}
+
+unsafe impl WsPin<SPI2> for PB12<AF5> {}
+
+unsafe impl WsPin<SPI3> for PA4<AF6> {}
+unsafe impl WsPin<SPI3> for PA15<AF6> {}
+
+unsafe impl SdPin<SPI2> for PB15<AF5> {}
+
+unsafe impl SdPin<SPI3> for PB5<AF6> {}
+unsafe impl SdPin<SPI3> for PC12<AF6> {}
+
+unsafe impl ExtSdPin<SPI2> for PB14<AF5> {}
+
+unsafe impl ExtSdPin<SPI3> for PB4<AF6> {}
+unsafe impl ExtSdPin<SPI3> for PC11<AF6> {}
+
+unsafe impl MckPin<SPI2> for PA8<AF5> {}
+unsafe impl MckPin<SPI2> for PC6<AF6> {}
+
+unsafe impl MckPin<SPI3> for PC7<AF6> {}
+
+/// Operating mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Generates the clocks and transmits
+    MasterTransmit,
+    /// Generates the clocks and receives
+    MasterReceive,
+    /// Uses external clocks and transmits
+    SlaveTransmit,
+    /// Uses external clocks and receives
+    SlaveReceive,
+}
+
+impl Mode {
+    /// Conversion to bits for I2SCFG in SPI_I2SCFGR
+    fn bitcode(self) -> u8 {
+        match self {
+            Mode::SlaveTransmit => 0b00,
+            Mode::SlaveReceive => 0b01,
+            Mode::MasterTransmit => 0b10,
+            Mode::MasterReceive => 0b11,
+        }
+    }
+
+    fn is_master(self) -> bool {
+        self == Mode::MasterTransmit || self == Mode::MasterReceive
+    }
+
+    fn is_transmit(self) -> bool {
+        self == Mode::MasterTransmit || self == Mode::SlaveTransmit
+    }
+}
+
+/// Audio standard
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Standard {
+    /// Philips I2S
+    Philips,
+    /// MSB justified (left justified)
+    MsbJustified,
+    /// LSB justified (right justified)
+    LsbJustified,
+    /// PCM with a short frame synchronization pulse
+    PcmShortSync,
+    /// PCM with a long frame synchronization pulse (13 clock cycles)
+    PcmLongSync,
+}
+
+impl Standard {
+    /// Conversion to bits for I2SSTD in SPI_I2SCFGR
+    fn bitcode(self) -> u8 {
+        match self {
+            Standard::Philips => 0b00,
+            Standard::MsbJustified => 0b01,
+            Standard::LsbJustified => 0b10,
+            Standard::PcmShortSync | Standard::PcmLongSync => 0b11,
+        }
+    }
+}
+
+/// Data length and channel length
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataFormat {
+    /// 16-bit data in a 16-bit channel
+    Data16Channel16,
+    /// 16-bit data in a 32-bit channel
+    Data16Channel32,
+    /// 24-bit data in a 32-bit channel
+    Data24Channel32,
+    /// 32-bit data in a 32-bit channel
+    Data32Channel32,
+}
+
+impl DataFormat {
+    /// Conversion to bits for DATLEN in SPI_I2SCFGR
+    fn datlen(self) -> u8 {
+        match self {
+            DataFormat::Data16Channel16 | DataFormat::Data16Channel32 => 0b00,
+            DataFormat::Data24Channel32 => 0b01,
+            DataFormat::Data32Channel32 => 0b10,
+        }
+    }
+
+    fn is_channel32(self) -> bool {
+        self != DataFormat::Data16Channel16
+    }
+
+    /// Splits a right-aligned sample into the half-words written to the data register
+    fn split(self, sample: u32) -> ([u16; 2], usize) {
+        match self {
+            DataFormat::Data16Channel16 | DataFormat::Data16Channel32 => ([sample as u16, 0], 1),
+            // the 8 LSBs go into the upper byte of the second half-word
+            DataFormat::Data24Channel32 => ([(sample >> 8) as u16, (sample << 8) as u16], 2),
+            DataFormat::Data32Channel32 => ([(sample >> 16) as u16, sample as u16], 2),
+        }
+    }
+
+    /// Joins the half-words read from the data register into a right-aligned sample
+    fn join(self, half_words: [u16; 2]) -> u32 {
+        let (msb, lsb) = (u32::from(half_words[0]), u32::from(half_words[1]));
+        match self {
+            DataFormat::Data16Channel16 | DataFormat::Data16Channel32 => msb,
+            DataFormat::Data24Channel32 => (msb << 8) | (lsb >> 8),
+            DataFormat::Data32Channel32 => (msb << 16) | lsb,
+        }
+    }
+
+    fn half_words(self) -> usize {
+        self.split(0).1
+    }
+}
+
+/// Channel of a stereo frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// Left channel
+    Left,
+    /// Right channel
+    Right,
+}
+
+/// I2S configuration
+///
+/// Defaults to the Philips standard with 16-bit data in 16-bit channels and a clock, which
+/// is low when idle.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    mode: Mode,
+    standard: Standard,
+    format: DataFormat,
+    polarity: Polarity,
+}
+
+impl Config {
+    /// Creates a configuration for `mode`
+    pub fn new(mode: Mode) -> Self {
+        Config {
+            mode,
+            standard: Standard::Philips,
+            format: DataFormat::Data16Channel16,
+            polarity: Polarity::IdleLow,
+        }
+    }
+
+    /// Sets the audio standard
+    pub fn standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Sets the data and channel length
+    pub fn data_format(mut self, format: DataFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the idle state of the serial clock
+    pub fn clock_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+}
+
+impl From<Mode> for Config {
+    fn from(mode: Mode) -> Self {
+        Config::new(mode)
+    }
+}
+
+/// Interrupt event
+pub enum Event {
+    /// New data has been received
+    Rxne,
+    /// New data can be sent
+    Txe,
+    /// An overrun, underrun or frame error occurred
+    Error,
+}
+
+/// I2S peripheral
+pub struct I2s<SPI, PINS> {
+    i2s: SPI,
+    pins: PINS,
+    config: Config,
+    sample_rate: Option<Hertz>,
+}
+
+/// I2S extension block, operating in the opposite direction of its main peripheral
+pub struct I2sExt<EXT, PIN> {
+    i2s: EXT,
+    pin: PIN,
+    config: Config,
+}
+
+/// Calculates I2SDIV and ODD for the sample rate `fs` and returns them together with the
+/// sample rate, which is actually achieved
+fn prescaler(i2sclk: u32, fs: u32, format: DataFormat, mclk: bool) -> (u8, bool, Hertz) {
+    // the bit clock is 32 or 64 times the sample rate, the master clock is always 256 times
+    let factor = if mclk {
+        256
+    } else if format.is_channel32() {
+        64
+    } else {
+        32
+    };
+
+    // rounded value of 2 * I2SDIV + ODD
+    let div = (i2sclk + factor * fs / 2) / (factor * fs);
+    assert!((4..=511).contains(&div), "I2S prescaler out of range");
+
+    (
+        (div / 2) as u8,
+        div % 2 == 1,
+        Hertz(i2sclk / (factor * div)),
+    )
+}
+
+macro_rules! i2scfgr {
+    ($w:ident, $config:expr, $mode:expr) => {
+        // NOTE(unsafe) the bit codes are valid for the respective fields
+        unsafe {
+            $w.i2smod()
+                .set_bit()
+                .i2se()
+                .clear_bit()
+                .i2scfg()
+                .bits($mode.bitcode())
+                .i2sstd()
+                .bits($config.standard.bitcode())
+                .pcmsync()
+                .bit($config.standard == Standard::PcmLongSync)
+                .ckpol()
+                .bit($config.polarity == Polarity::IdleHigh)
+                .datlen()
+                .bits($config.format.datlen())
+                .chlen()
+                .bit($config.format.is_channel32())
+        }
+    };
+}
+
+macro_rules! data {
+    ($I2s:ident, $SPIX:ident) => {
+        impl<PINS> $I2s<$SPIX, PINS> {
+            /// Starts listening for an interrupt event
+            pub fn listen(&mut self, event: Event) {
+                match event {
+                    Event::Rxne => self.i2s.cr2.modify(|_, w| w.rxneie().set_bit()),
+                    Event::Txe => self.i2s.cr2.modify(|_, w| w.txeie().set_bit()),
+                    Event::Error => self.i2s.cr2.modify(|_, w| w.errie().set_bit()),
+                }
+            }
+
+            /// Stops listening for an interrupt event
+            pub fn unlisten(&mut self, event: Event) {
+                match event {
+                    Event::Rxne => self.i2s.cr2.modify(|_, w| w.rxneie().clear_bit()),
+                    Event::Txe => self.i2s.cr2.modify(|_, w| w.txeie().clear_bit()),
+                    Event::Error => self.i2s.cr2.modify(|_, w| w.errie().clear_bit()),
+                }
+            }
+
+            /// Enables the I2S
+            pub fn enable(&mut self) {
+                self.i2s.i2scfgr.modify(|_, w| w.i2se().set_bit());
+            }
+
+            /// Disables the I2S
+            ///
+            /// To avoid a truncated frame, wait for the right channel to be on the bus first.
+            pub fn disable(&mut self) {
+                self.i2s.i2scfgr.modify(|_, w| w.i2se().clear_bit());
+            }
+
+            /// Returns the channel of the half-word to be sent next or the one just received
+            ///
+            /// This is meaningless in the PCM standards.
+            pub fn side(&self) -> Side {
+                if self.i2s.sr.read().chside().bit_is_set() {
+                    Side::Right
+                } else {
+                    Side::Left
+                }
+            }
+
+            /// Returns and clears a pending overrun, underrun or frame error
+            fn check_errors(&mut self) -> Result<(), Error> {
+                let sr = self.i2s.sr.read();
+
+                if sr.fre().bit_is_set() {
+                    // cleared by reading SR
+                    Err(Error::Frame)
+                } else if sr.ovr().bit_is_set() {
+                    // cleared by reading DR, then SR
+                    // NOTE(read_volatile) see note in `read`
+                    let _ = unsafe { ptr::read_volatile(&self.i2s.dr as *const _ as *const u16) };
+                    let _ = self.i2s.sr.read();
+                    Err(Error::Overrun)
+                } else if sr.udr().bit_is_set() {
+                    // cleared by reading SR
+                    Err(Error::Underrun)
+                } else {
+                    Ok(())
+                }
+            }
+
+            /// Sends a half-word
+            pub fn send(&mut self, half_word: u16) -> nb::Result<(), Error> {
+                self.check_errors()?;
+
+                if self.i2s.sr.read().txe().bit_is_set() {
+                    // NOTE(write_volatile) see note in `read`
+                    unsafe { ptr::write_volatile(&self.i2s.dr as *const _ as *mut u16, half_word) }
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Reads a half-word
+            pub fn read(&mut self) -> nb::Result<u16, Error> {
+                self.check_errors()?;
+
+                if self.i2s.sr.read().rxne().bit_is_set() {
+                    // NOTE(read_volatile) the register is always accessed with 16 bits in I2S
+                    // mode
+                    Ok(unsafe { ptr::read_volatile(&self.i2s.dr as *const _ as *const u16) })
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Sends a stereo frame, blocking until all half-words are written
+            ///
+            /// The samples are right-aligned, e.g. a 24-bit sample occupies bits 0 to 23.
+            pub fn write_frame(&mut self, left: u32, right: u32) -> Result<(), Error> {
+                for &sample in &[left, right] {
+                    let (half_words, len) = self.config.format.split(sample);
+                    for &half_word in &half_words[..len] {
+                        nb::block!(self.send(half_word))?;
+                    }
+                }
+
+                Ok(())
+            }
+
+            /// Reads a stereo frame as right-aligned samples, blocking until it is received
+            pub fn read_frame(&mut self) -> Result<(u32, u32), Error> {
+                let mut frame = [0; 2];
+
+                for sample in frame.iter_mut() {
+                    let mut half_words = [0; 2];
+                    for half_word in half_words[..self.config.format.half_words()].iter_mut() {
+                        *half_word = nb::block!(self.read())?;
+                    }
+                    *sample = self.config.format.join(half_words);
+                }
+
+                Ok((frame[0], frame[1]))
+            }
+
+            /// Continuously sends `buffer` using the DMA in circular mode
+            ///
+            /// The buffer holds half-words as written to the data register.
+            pub fn write_dma_circular<TX>(
+                self,
+                buffer: &'static mut [u16],
+                mut channel: TX,
+            ) -> CircBuffer<u16, TX, Self>
+            where
+                TX: dma::TxChannel<$SPIX>,
+            {
+                // NOTE(unsafe) DR is accessed with 16 bits in I2S mode
+                unsafe {
+                    channel.set_peripheral_address(
+                        &self.i2s.dr as *const _ as u32,
+                        dma::Increment::Disable,
+                    );
+                }
+
+                CircBuffer::start_write(buffer, channel, self)
+            }
+
+            /// Continuously receives into `buffer` using the DMA in circular mode
+            ///
+            /// The buffer holds half-words as read from the data register.
+            pub fn read_dma_circular<RX>(
+                self,
+                buffer: &'static mut [u16],
+                mut channel: RX,
+            ) -> CircBuffer<u16, RX, Self>
+            where
+                RX: dma::RxChannel<$SPIX>,
+            {
+                // NOTE(unsafe) DR is accessed with 16 bits in I2S mode
+                unsafe {
+                    channel.set_peripheral_address(
+                        &self.i2s.dr as *const _ as u32,
+                        dma::Increment::Disable,
+                    );
+                }
+
+                CircBuffer::start_read(buffer, channel, self)
+            }
+        }
+
+        impl<PINS> dma::Target for $I2s<$SPIX, PINS> {
+            type Error = Void;
+
+            /// Enables the DMA request and then the I2S, so the first frames are already
+            /// served by the DMA
+            fn enable_dma(&mut self) {
+                if self.config.mode.is_transmit() {
+                    self.i2s.cr2.modify(|_, w| w.txdmaen().set_bit());
+                } else {
+                    self.i2s.cr2.modify(|_, w| w.rxdmaen().set_bit());
+                }
+                self.enable();
+            }
+
+            fn disable_dma(&mut self) {
+                self.disable();
+                self.i2s
+                    .cr2
+                    .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+            }
+        }
+    };
+}
+
+macro_rules! hal {
+    ($($SPIX:ident: ($i2sX:ident, $EXT:ident, $i2sXext:ident, $spiXen:ident, $spiXrst:ident),)+) => {
+        $(
+            impl<CK, WS, SD, MCK> I2s<$SPIX, (CK, WS, SD, MCK)> {
+                /// Configures the SPI peripheral to operate as I2S interface
+                ///
+                /// `sample_rate` is only used by the master modes, which derive the clocks
+                /// from the I2S clock (see `rcc::CFGR::i2s_ckin`). With a pin in place of
+                /// `NoMck`, the master clock is output at 256 times the sample rate.
+                ///
+                /// The I2S starts disabled. It is enabled with `enable` or by starting a
+                /// DMA transfer.
+                ///
+                /// # Panics
+                ///
+                /// Panics in the master modes, if the I2S clock divided by the master
+                /// clock (256 times the sample rate) or else by the bit clock (32 or 64
+                /// times the sample rate) is not in the range 4 to 511.
+                pub fn $i2sX<C, F>(
+                    spi: $SPIX,
+                    pins: (CK, WS, SD, MCK),
+                    config: C,
+                    sample_rate: F,
+                    clocks: Clocks,
+                    apb1: &mut APB1,
+                ) -> Self
+                where
+                    C: Into<Config>,
+                    F: Into<Hertz>,
+                    CK: CkPin<$SPIX>,
+                    WS: WsPin<$SPIX>,
+                    SD: SdPin<$SPIX>,
+                    MCK: MckPin<$SPIX>,
+                {
+                    // enable or reset $SPIX
+                    apb1.enr().modify(|_, w| w.$spiXen().enabled());
+                    apb1.rstr().modify(|_, w| w.$spiXrst().reset());
+                    apb1.rstr().modify(|_, w| w.$spiXrst().clear_bit());
+
+                    let config = config.into();
+
+                    let sample_rate = if config.mode.is_master() {
+                        let (i2sdiv, odd, actual) = prescaler(
+                            clocks.i2sclk().0,
+                            sample_rate.into().0,
+                            config.format,
+                            MCK::OUTPUT,
+                        );
+
+                        // NOTE(unsafe) I2SDIV is checked to be in range by `prescaler`
+                        spi.i2spr.write(|w| unsafe {
+                            w.i2sdiv()
+                                .bits(i2sdiv)
+                                .odd()
+                                .bit(odd)
+                                .mckoe()
+                                .bit(MCK::OUTPUT)
+                        });
+
+                        Some(actual)
+                    } else {
+                        None
+                    };
+
+                    spi.i2scfgr.write(|w| i2scfgr!(w, config, config.mode));
+
+                    I2s {
+                        i2s: spi,
+                        pins,
+                        config,
+                        sample_rate,
+                    }
+                }
+
+                /// Returns the sample rate achieved by the clock dividers in the master modes
+                pub fn sample_rate(&self) -> Option<Hertz> {
+                    self.sample_rate
+                }
+
+                /// Configures the extension block for full duplex operation
+                ///
+                /// The extension block is a slave, which transfers data in the opposite
+                /// direction. It starts disabled and has to be enabled, or its DMA transfer
+                /// started, before the I2S, so it doesn't miss the first frame.
+                pub fn $i2sXext<PIN>(&mut self, ext: $EXT, pin: PIN) -> I2sExt<$EXT, PIN>
+                where
+                    PIN: ExtSdPin<$SPIX>,
+                {
+                    let mode = if self.config.mode.is_transmit() {
+                        Mode::SlaveReceive
+                    } else {
+                        Mode::SlaveTransmit
+                    };
+
+                    ext.i2scfgr.write(|w| i2scfgr!(w, self.config, mode));
+
+                    I2sExt {
+                        i2s: ext,
+                        pin,
+                        config: Config { mode, ..self.config },
+                    }
+                }
+
+                /// Releases the SPI peripheral and associated pins
+                pub fn free(self) -> ($SPIX, (CK, WS, SD, MCK)) {
+                    self.i2s.i2scfgr.modify(|_, w| w.i2se().clear_bit());
+
+                    (self.i2s, self.pins)
+                }
+            }
+
+            impl<PIN> I2sExt<$EXT, PIN> {
+                /// Releases the extension block and its pin
+                pub fn free(self) -> ($EXT, PIN) {
+                    self.i2s.i2scfgr.modify(|_, w| w.i2se().clear_bit());
+
+                    (self.i2s, self.pin)
+                }
+            }
+
+            data!(I2s, $SPIX);
+            data!(I2sExt, $EXT);
+        )+
+    }
+}
+
+hal! {
+    SPI2: (i2s2, I2S2EXT, i2s2ext, spi2en, spi2rst),
+    SPI3: (i2s3, I2S3EXT, i2s3ext, spi3en, spi3rst),
+}
+
+// the extension blocks share the DMA requests of their main peripheral in the opposite
+// direction
+unsafe impl dma::RxChannel<I2S2EXT> for dma1::C4 {}
+unsafe impl dma::TxChannel<I2S2EXT> for dma1::C5 {}
+
+unsafe impl dma::RxChannel<I2S3EXT> for dma2::C1 {}
+unsafe impl dma::TxChannel<I2S3EXT> for dma2::C2 {}
@@ -26,6 +26,14 @@ impl RccExt for RCC {
                 pclk1: None,
                 pclk2: None,
                 sysclk: None,
+                i2c1clk: I2cClock::Hsi,
+                i2c2clk: I2cClock::Hsi,
+                i2c3clk: I2cClock::Hsi,
+                i2s_ckin: None,
+                #[cfg(feature = "stm32f373")]
+                adcclk: None,
+                #[cfg(feature = "stm32f373")]
+                sdadcclk: None,
             },
         }
     }
@@ -96,6 +104,15 @@ impl APB2 {
 
 const HSI: u32 = 8_000_000; // Hz
 
+/// Kernel clock source of an I2C peripheral (I2CxSW in RCC_CFGR3)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum I2cClock {
+    /// High speed internal oscillator (8 MHz), the reset value
+    Hsi,
+    /// System clock
+    Sysclk,
+}
+
 // some microcontrollers do not have USB
 #[cfg(any(feature = "stm32f301", feature = "stm32f334",))]
 mod usb_clocking {
@@ -139,6 +156,14 @@ pub struct CFGR {
     pclk1: Option<u32>,
     pclk2: Option<u32>,
     sysclk: Option<u32>,
+    i2c1clk: I2cClock,
+    i2c2clk: I2cClock,
+    i2c3clk: I2cClock,
+    i2s_ckin: Option<u32>,
+    #[cfg(feature = "stm32f373")]
+    adcclk: Option<u32>,
+    #[cfg(feature = "stm32f373")]
+    sdadcclk: Option<u32>,
 }
 
 impl CFGR {
@@ -188,6 +213,71 @@ impl CFGR {
         self
     }
 
+    /// Selects the kernel clock source of I2C1 (defaults to HSI)
+    pub fn i2c1clk(mut self, source: I2cClock) -> Self {
+        self.i2c1clk = source;
+        self
+    }
+
+    /// Selects the kernel clock source of I2C2 (defaults to HSI)
+    pub fn i2c2clk(mut self, source: I2cClock) -> Self {
+        self.i2c2clk = source;
+        self
+    }
+
+    /// Selects the kernel clock source of I2C3 (defaults to HSI)
+    pub fn i2c3clk(mut self, source: I2cClock) -> Self {
+        self.i2c3clk = source;
+        self
+    }
+
+    /// Clocks the I2S peripherals from the external I2S_CKIN pin instead of the system clock
+    ///
+    /// `freq` is the frequency of the clock applied to the pin.
+    #[cfg(any(
+        feature = "stm32f303xb",
+        feature = "stm32f303xc",
+        feature = "stm32f303xd",
+        feature = "stm32f303xe",
+        feature = "stm32f358",
+        feature = "stm32f398"
+    ))]
+    pub fn i2s_ckin<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.i2s_ckin = Some(freq.into().0);
+        self
+    }
+
+    /// Sets the maximum frequency of the ADC clock, which is divided from the APB2
+    /// clock (defaults to 14 MHz, the highest possible frequency)
+    ///
+    /// Higher frequencies are limited to 14 MHz. `freeze` panics if even the largest
+    /// divider of 8 exceeds the frequency.
+    #[cfg(feature = "stm32f373")]
+    pub fn adcclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.adcclk = Some(freq.into().0);
+        self
+    }
+
+    /// Sets the maximum frequency of the SDADC clock, which is divided from the system
+    /// clock (defaults to 6 MHz, the frequency of the fast mode)
+    ///
+    /// Higher frequencies are limited to 6.3 MHz. `freeze` panics if even the largest
+    /// divider of 48 exceeds the frequency.
+    #[cfg(feature = "stm32f373")]
+    pub fn sdadcclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.sdadcclk = Some(freq.into().0);
+        self
+    }
+
     /// Returns a tuple of the (pllsrclk frequency, pllmul, and pllsrc).
     #[cfg(not(any(
         feature = "stm32f302",
@@ -368,6 +458,90 @@ impl CFGR {
                 })
         });
 
+        // select the I2C kernel clocks
+        rcc.cfgr3.modify(|_, w| {
+            w.i2c1sw().bit(self.i2c1clk == I2cClock::Sysclk);
+            #[cfg(not(feature = "stm32f334"))]
+            w.i2c2sw().bit(self.i2c2clk == I2cClock::Sysclk);
+            #[cfg(any(
+                feature = "stm32f301",
+                feature = "stm32f318",
+                feature = "stm32f303xd",
+                feature = "stm32f303xe",
+                feature = "stm32f398",
+            ))]
+            w.i2c3sw().bit(self.i2c3clk == I2cClock::Sysclk);
+            w
+        });
+
+        // select the I2S clock
+        #[cfg(any(
+            feature = "stm32f303xb",
+            feature = "stm32f303xc",
+            feature = "stm32f303xd",
+            feature = "stm32f303xe",
+            feature = "stm32f358",
+            feature = "stm32f398"
+        ))]
+        rcc.cfgr
+            .modify(|_, w| w.i2ssrc().bit(self.i2s_ckin.is_some()));
+
+        // ADCPRE: the smallest divider of the APB2 clock, which doesn't exceed the
+        //         requested ADC clock, nor 14 MHz
+        #[cfg(feature = "stm32f373")]
+        let adcclk = {
+            let max = self.adcclk.unwrap_or(14_000_000).min(14_000_000);
+            let (adcpre_bits, adcpre) = [(0b00, 2), (0b01, 4), (0b10, 6), (0b11, 8)]
+                .iter()
+                .cloned()
+                .find(|&(_, div)| pclk2 / div <= max)
+                .unwrap_or((0b11, 8));
+            assert!(pclk2 / adcpre <= max);
+
+            rcc.cfgr
+                .modify(|_, w| unsafe { w.adcpre().bits(adcpre_bits) });
+            pclk2 / adcpre
+        };
+
+        // SDPRE: the smallest divider of the system clock, which doesn't exceed the
+        //        requested SDADC clock, nor 6.3 MHz
+        #[cfg(feature = "stm32f373")]
+        let sdadcclk = {
+            let max = self.sdadcclk.unwrap_or(6_000_000).min(6_300_000);
+            let (sdpre_bits, sdpre) = [
+                (0b10000, 2),
+                (0b10001, 4),
+                (0b10010, 6),
+                (0b10011, 8),
+                (0b10100, 10),
+                (0b10101, 12),
+                (0b10110, 14),
+                (0b10111, 16),
+                (0b11000, 20),
+                (0b11001, 24),
+                (0b11010, 28),
+                (0b11011, 32),
+                (0b11100, 36),
+                (0b11101, 40),
+                (0b11110, 44),
+                (0b11111, 48),
+            ]
+            .iter()
+            .cloned()
+            .find(|&(_, div)| sysclk / div <= max)
+            .unwrap_or((0b11111, 48));
+            assert!(sysclk / sdpre <= max);
+
+            rcc.cfgr
+                .modify(|_, w| unsafe { w.sdpre().bits(sdpre_bits) });
+            sysclk / sdpre
+        };
+
+        let i2cclk = |source| match source {
+            I2cClock::Hsi => Hertz(HSI),
+            I2cClock::Sysclk => Hertz(sysclk),
+        };
+
         Clocks {
             hclk: Hertz(hclk),
             pclk1: Hertz(pclk1),
@@ -376,6 +550,14 @@ impl CFGR {
             ppre2,
             sysclk: Hertz(sysclk),
             usbclk_valid,
+            i2c1clk: i2cclk(self.i2c1clk),
+            i2c2clk: i2cclk(self.i2c2clk),
+            i2c3clk: i2cclk(self.i2c3clk),
+            i2sclk: Hertz(self.i2s_ckin.unwrap_or(sysclk)),
+            #[cfg(feature = "stm32f373")]
+            adcclk: Hertz(adcclk),
+            #[cfg(feature = "stm32f373")]
+            sdadcclk: Hertz(sdadcclk),
         }
     }
 }
@@ -394,6 +576,14 @@ pub struct Clocks {
     ppre2: u8,
     sysclk: Hertz,
     usbclk_valid: bool,
+    i2c1clk: Hertz,
+    i2c2clk: Hertz,
+    i2c3clk: Hertz,
+    i2sclk: Hertz,
+    #[cfg(feature = "stm32f373")]
+    adcclk: Hertz,
+    #[cfg(feature = "stm32f373")]
+    sdadcclk: Hertz,
 }
 
 impl Clocks {
@@ -431,4 +621,36 @@ impl Clocks {
     pub fn usbclk_valid(&self) -> bool {
         self.usbclk_valid
     }
+
+    /// Returns the kernel clock frequency of I2C1
+    pub fn i2c1clk(&self) -> Hertz {
+        self.i2c1clk
+    }
+
+    /// Returns the kernel clock frequency of I2C2
+    pub fn i2c2clk(&self) -> Hertz {
+        self.i2c2clk
+    }
+
+    /// Returns the kernel clock frequency of I2C3
+    pub fn i2c3clk(&self) -> Hertz {
+        self.i2c3clk
+    }
+
+    /// Returns the clock frequency of the I2S peripherals
+    pub fn i2sclk(&self) -> Hertz {
+        self.i2sclk
+    }
+
+    /// Returns the frequency of the ADC clock
+    #[cfg(feature = "stm32f373")]
+    pub fn adcclk(&self) -> Hertz {
+        self.adcclk
+    }
+
+    /// Returns the frequency of the SDADC clock
+    #[cfg(feature = "stm32f373")]
+    pub fn sdadcclk(&self) -> Hertz {
+        self.sdadcclk
+    }
 }
@@ -0,0 +1,447 @@
+//! API for the SDADCs (16-bit Sigma-Delta Analog to Digital Converters)
+//!
+//! The STM32F373 has three SDADCs with up to 9 input channels each.
+//! Every SDADC holds three configurations (gain, single-ended or differential
+//! input and common mode), which are assigned to its channels. The reference
+//! voltage is selected once for all SDADCs through SDADC1.
+//!
+//! The channels are selected through their analog pins: the SDADCx_AINyP pin for
+//! a single-ended input, or a `Differential` pair of the SDADCx_AINyP and
+//! SDADCx_AINyM pins.
+//!
+//! The SDADCs are clocked from the system clock through SDPRE
+//! (see `rcc::CFGR::sdadcclk`).
+use embedded_hal::adc::{Channel, OneShot};
+
+use crate::gpio::{gpiob, gpiod, gpioe, Analog};
+use crate::rcc::{APB1, APB2};
+use crate::stm32::{PWR, SDADC1, SDADC2, SDADC3};
+
+/// Reference voltage of all SDADCs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reference {
+    /// External reference on the VREFSD+ pin
+    External,
+    /// Internal 1.2 V reference
+    Internal1_2V,
+    /// Internal 1.8 V reference
+    Internal1_8V,
+    /// The SDADC supply VDDSD
+    Vdd,
+}
+
+impl Reference {
+    /// Conversion to bits for REFV in SDADC1_CR1
+    fn bitcode(&self) -> u8 {
+        match self {
+            Reference::External => 0b00,
+            Reference::Internal1_2V => 0b01,
+            Reference::Internal1_8V => 0b10,
+            Reference::Vdd => 0b11,
+        }
+    }
+}
+
+/// Gain of the input amplifier
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gain {
+    X0_5,
+    X1,
+    X2,
+    X4,
+    X8,
+    X16,
+    X32,
+}
+
+impl Gain {
+    /// Conversion to bits for GAINx in SDADC_CONFxR
+    fn bitcode(&self) -> u8 {
+        match self {
+            Gain::X1 => 0b000,
+            Gain::X2 => 0b001,
+            Gain::X4 => 0b010,
+            Gain::X8 => 0b011,
+            Gain::X16 => 0b100,
+            Gain::X32 => 0b101,
+            Gain::X0_5 => 0b111,
+        }
+    }
+}
+
+/// Input mode of a configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputMode {
+    /// Differential input between the channel's P and N pins
+    Differential,
+    /// Single-ended input, the results are offset by half the range
+    SingleEndedOffset,
+    /// Single-ended input, zero volts convert to the lowest result
+    SingleEndedZeroReference,
+}
+
+impl InputMode {
+    /// Conversion to bits for SEx in SDADC_CONFxR
+    fn bitcode(&self) -> u8 {
+        match self {
+            InputMode::Differential => 0b00,
+            InputMode::SingleEndedOffset => 0b01,
+            InputMode::SingleEndedZeroReference => 0b11,
+        }
+    }
+}
+
+/// Common mode of a configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommonMode {
+    /// VSSSD
+    Vss,
+    /// VREFSD+
+    VRef,
+    /// VDDSD / 2
+    HalfVdd,
+}
+
+impl CommonMode {
+    /// Conversion to bits for COMMONx in SDADC_CONFxR
+    fn bitcode(&self) -> u8 {
+        match self {
+            CommonMode::Vss => 0b00,
+            CommonMode::VRef => 0b01,
+            CommonMode::HalfVdd => 0b10,
+        }
+    }
+}
+
+/// One of the three configuration registers of a SDADC
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigSlot {
+    Conf0,
+    Conf1,
+    Conf2,
+}
+
+/// Configuration of the conversions of the channels, which it is assigned to
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    gain: Gain,
+    input_mode: InputMode,
+    common_mode: CommonMode,
+    offset: u16,
+}
+
+impl Config {
+    /// Creates a single-ended configuration with gain 1
+    pub fn new() -> Self {
+        Config {
+            gain: Gain::X1,
+            input_mode: InputMode::SingleEndedOffset,
+            common_mode: CommonMode::Vss,
+            offset: 0,
+        }
+    }
+
+    /// Sets the gain of the input amplifier
+    pub fn gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Sets the input mode
+    pub fn input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Sets the common mode
+    pub fn common_mode(mut self, common_mode: CommonMode) -> Self {
+        self.common_mode = common_mode;
+        self
+    }
+
+    /// Sets the 12-bit offset, which is subtracted from the results
+    ///
+    /// Note that `Sdadc::calibrate` overwrites the offsets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offset is wider than 12 bits.
+    pub fn offset(mut self, offset: u16) -> Self {
+        assert!(offset < 1 << 12);
+        self.offset = offset;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The P and N pins of a differential input
+///
+/// Its channel is the one of the P pin. Convert it with a configuration in
+/// `InputMode::Differential`.
+pub struct Differential<P, N> {
+    p: P,
+    n: N,
+}
+
+impl<P, N> Differential<P, N> {
+    /// Pairs the P pin `p` with the N pin `n`
+    pub fn new(p: P, n: N) -> Self {
+        Differential { p, n }
+    }
+
+    /// Releases the pins
+    pub fn free(self) -> (P, N) {
+        (self.p, self.n)
+    }
+}
+
+/// Sigma-Delta Analog to Digital Converter
+pub struct Sdadc<SDADC> {
+    rb: SDADC,
+}
+
+macro_rules! sdadc_pins {
+    ($SDADC:ident, $($pin:ty => $chan:expr),+ $(,)*) => {
+        $(
+            impl Channel<$SDADC> for $pin {
+                type ID = u8;
+
+                fn channel() -> u8 { $chan }
+            }
+        )+
+    };
+}
+
+// # SDADC1 Pin/Channel mapping
+
+sdadc_pins!(SDADC1,
+    gpioe::PE12<Analog> => 0_u8,
+    gpioe::PE11<Analog> => 1_u8,
+    gpioe::PE10<Analog> => 2_u8,
+    gpioe::PE7<Analog> => 3_u8,
+    gpiob::PB2<Analog> => 4_u8,
+    gpiob::PB1<Analog> => 5_u8,
+    gpiob::PB0<Analog> => 6_u8,
+    gpioe::PE9<Analog> => 7_u8,
+    gpioe::PE8<Analog> => 8_u8,
+    Differential<gpioe::PE12<Analog>, gpioe::PE13<Analog>> => 0_u8,
+    Differential<gpioe::PE10<Analog>, gpioe::PE11<Analog>> => 2_u8,
+    Differential<gpiob::PB2<Analog>, gpioe::PE7<Analog>> => 4_u8,
+    Differential<gpiob::PB0<Analog>, gpiob::PB1<Analog>> => 6_u8,
+    Differential<gpioe::PE8<Analog>, gpioe::PE9<Analog>> => 8_u8,
+);
+
+// # SDADC2 Pin/Channel mapping
+
+sdadc_pins!(SDADC2,
+    gpioe::PE14<Analog> => 0_u8,
+    gpioe::PE13<Analog> => 1_u8,
+    gpioe::PE12<Analog> => 2_u8,
+    gpioe::PE11<Analog> => 3_u8,
+    gpioe::PE10<Analog> => 4_u8,
+    gpioe::PE7<Analog> => 5_u8,
+    gpiob::PB2<Analog> => 6_u8,
+    gpioe::PE9<Analog> => 7_u8,
+    gpioe::PE8<Analog> => 8_u8,
+    Differential<gpioe::PE14<Analog>, gpioe::PE15<Analog>> => 0_u8,
+    Differential<gpioe::PE12<Analog>, gpioe::PE13<Analog>> => 2_u8,
+    Differential<gpioe::PE10<Analog>, gpioe::PE11<Analog>> => 4_u8,
+    Differential<gpiob::PB2<Analog>, gpioe::PE7<Analog>> => 6_u8,
+    Differential<gpioe::PE8<Analog>, gpioe::PE9<Analog>> => 8_u8,
+);
+
+// # SDADC3 Pin/Channel mapping
+
+sdadc_pins!(SDADC3,
+    gpiod::PD14<Analog> => 0_u8,
+    gpiod::PD13<Analog> => 1_u8,
+    gpiod::PD12<Analog> => 2_u8,
+    gpiod::PD11<Analog> => 3_u8,
+    gpiod::PD10<Analog> => 4_u8,
+    gpiod::PD9<Analog> => 5_u8,
+    gpiod::PD8<Analog> => 6_u8,
+    gpiob::PB15<Analog> => 7_u8,
+    gpiob::PB14<Analog> => 8_u8,
+    Differential<gpiod::PD14<Analog>, gpiod::PD15<Analog>> => 0_u8,
+    Differential<gpiod::PD12<Analog>, gpiod::PD13<Analog>> => 2_u8,
+    Differential<gpiod::PD10<Analog>, gpiod::PD11<Analog>> => 4_u8,
+    Differential<gpiod::PD8<Analog>, gpiod::PD9<Analog>> => 6_u8,
+    Differential<gpiob::PB14<Analog>, gpiob::PB15<Analog>> => 8_u8,
+);
+
+macro_rules! hal {
+    ($($SDADCX:ident: ($sdadcX:ident, $ensdX:ident, $sdadcXen:ident, $sdadcXrst:ident),)+) => {
+        $(
+            impl Sdadc<$SDADCX> {
+                /// Init a new SDADC
+                ///
+                /// Powers the analog part up, enables the clock, resets the SDADC and turns
+                /// it on
+                pub fn $sdadcX(
+                    rb: $SDADCX,
+                    pwr: &mut PWR,
+                    apb1: &mut APB1,
+                    apb2: &mut APB2,
+                ) -> Self {
+                    apb1.enr().modify(|_, w| w.pwren().set_bit());
+                    pwr.cr.modify(|_, w| w.$ensdX().set_bit());
+                    apb2.enr().modify(|_, w| w.$sdadcXen().set_bit());
+                    apb2.rstr().modify(|_, w| w.$sdadcXrst().set_bit());
+                    apb2.rstr().modify(|_, w| w.$sdadcXrst().clear_bit());
+
+                    let sdadc = Sdadc { rb };
+                    sdadc.rb.cr2.modify(|_, w| w.adon().set_bit());
+                    // STABIP is set a few SDADC clock cycles after ADON, and cleared
+                    // once the SDADC is stable
+                    while sdadc.rb.isr.read().stabip().bit_is_clear() {}
+                    while sdadc.rb.isr.read().stabip().bit_is_set() {}
+                    sdadc
+                }
+
+                /// Writes `config` into `slot`
+                pub fn set_config(&mut self, slot: ConfigSlot, config: Config) {
+                    let common = config.common_mode.bitcode();
+                    let se = config.input_mode.bitcode();
+                    let gain = config.gain.bitcode();
+                    let offset = config.offset;
+                    // NOTE(unsafe) Use only predefined, valid values and a 12-bit offset
+                    self.in_init_mode(|rb| unsafe {
+                        match slot {
+                            ConfigSlot::Conf0 => rb.conf0r.write(|w| w
+                                .common0().bits(common)
+                                .se0().bits(se)
+                                .gain0().bits(gain)
+                                .offset0().bits(offset)
+                            ),
+                            ConfigSlot::Conf1 => rb.conf1r.write(|w| w
+                                .common1().bits(common)
+                                .se1().bits(se)
+                                .gain1().bits(gain)
+                                .offset1().bits(offset)
+                            ),
+                            ConfigSlot::Conf2 => rb.conf2r.write(|w| w
+                                .common2().bits(common)
+                                .se2().bits(se)
+                                .gain2().bits(gain)
+                                .offset2().bits(offset)
+                            ),
+                        }
+                    });
+                }
+
+                /// Assigns the configuration in `slot` to the conversions of the channel
+                /// of `pin`
+                ///
+                /// All channels use `ConfigSlot::Conf0` by default.
+                pub fn assign_config<PIN>(&mut self, _pin: &PIN, slot: ConfigSlot)
+                where
+                    PIN: Channel<$SDADCX, ID = u8>,
+                {
+                    let slot = slot as u8;
+                    // NOTE(unsafe) CONFCHx holds a valid configuration slot
+                    self.in_init_mode(|rb| unsafe {
+                        match PIN::channel() {
+                            0 => rb.confchr1.modify(|_, w| w.confch0().bits(slot)),
+                            1 => rb.confchr1.modify(|_, w| w.confch1().bits(slot)),
+                            2 => rb.confchr1.modify(|_, w| w.confch2().bits(slot)),
+                            3 => rb.confchr1.modify(|_, w| w.confch3().bits(slot)),
+                            4 => rb.confchr1.modify(|_, w| w.confch4().bits(slot)),
+                            5 => rb.confchr1.modify(|_, w| w.confch5().bits(slot)),
+                            6 => rb.confchr1.modify(|_, w| w.confch6().bits(slot)),
+                            7 => rb.confchr1.modify(|_, w| w.confch7().bits(slot)),
+                            8 => rb.confchr2.modify(|_, w| w.confch8().bits(slot)),
+                            _ => unreachable!(),
+                        }
+                    });
+                }
+
+                /// Calibrates the offsets of all three configurations
+                ///
+                /// Repeat the calibration after changing a configuration.
+                pub fn calibrate(&mut self) {
+                    // NOTE(unsafe) CALIBCNT = 0b10 calibrates CONF0R to CONF2R
+                    self.in_init_mode(|rb| {
+                        rb.cr2.modify(|_, w| unsafe { w.calibcnt().bits(0b10) });
+                    });
+                    // STARTCALIB is cleared by hardware, CALIBIP is only set a few SDADC
+                    // clock cycles later, so the end is signalled by EOCALF
+                    self.rb.clrisr.write(|w| w.clreocalf().set_bit());
+                    self.rb.cr2.modify(|_, w| w.startcalib().set_bit());
+                    while self.rb.isr.read().eocalf().bit_is_clear() {}
+                    self.rb.clrisr.write(|w| w.clreocalf().set_bit());
+                }
+
+                /// Converts the channel of `pin` once, using the configuration assigned
+                /// to it
+                ///
+                /// The result is signed: in `InputMode::SingleEndedZeroReference`
+                /// zero volts convert to `-32768`.
+                pub fn convert<PIN>(&mut self, _pin: &PIN) -> i16
+                where
+                    PIN: Channel<$SDADCX, ID = u8>,
+                {
+                    // NOTE(unsafe) RCH holds a valid channel number
+                    self.rb.cr2.modify(|_, w| unsafe { w.rch().bits(PIN::channel()) });
+                    self.rb.cr2.modify(|_, w| w.rswstart().set_bit());
+                    while self.rb.isr.read().reocf().bit_is_clear() {}
+                    // reading RDATAR clears REOCF
+                    self.rb.rdatar.read().rdata().bits() as i16
+                }
+
+                /// Turns the SDADC off, powers its analog part down, disables the clock
+                /// and releases the peripheral
+                pub fn free(self, pwr: &mut PWR, apb2: &mut APB2) -> $SDADCX {
+                    self.rb.cr2.modify(|_, w| w.adon().clear_bit());
+                    pwr.cr.modify(|_, w| w.$ensdX().clear_bit());
+                    apb2.enr().modify(|_, w| w.$sdadcXen().clear_bit());
+                    self.rb
+                }
+
+                /// Runs `f` in initialization mode, in which the configuration is writable
+                fn in_init_mode<F>(&mut self, f: F)
+                where
+                    F: FnOnce(&$SDADCX),
+                {
+                    self.rb.cr1.modify(|_, w| w.init().set_bit());
+                    while self.rb.isr.read().initrdy().bit_is_clear() {}
+                    f(&self.rb);
+                    self.rb.cr1.modify(|_, w| w.init().clear_bit());
+                }
+            }
+
+            impl<WORD, PIN> OneShot<$SDADCX, WORD, PIN> for Sdadc<$SDADCX>
+            where
+                WORD: From<i16>,
+                PIN: Channel<$SDADCX, ID = u8>,
+            {
+                type Error = ();
+
+                fn read(&mut self, pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+                    Ok(self.convert(pin).into())
+                }
+            }
+        )+
+    }
+}
+
+hal! {
+    SDADC1: (sdadc1, ensd1, sdadc1en, sdadc1rst),
+    SDADC2: (sdadc2, ensd2, sdadc2en, sdadc2rst),
+    SDADC3: (sdadc3, ensd3, sdadc3en, sdadc3rst),
+}
+
+impl Sdadc<SDADC1> {
+    /// Selects the reference voltage of all three SDADCs
+    pub fn set_reference(&mut self, reference: Reference) {
+        // NOTE(unsafe) Use only predefined, valid values.
+        self.in_init_mode(|rb| {
+            rb.cr1
+                .modify(|_, w| unsafe { w.refv().bits(reference.bitcode()) });
+        });
+    }
+}
@@ -1,14 +1,32 @@
 //! Serial
 
+use core::cmp;
+use core::fmt;
 use core::marker::PhantomData;
 use core::ptr;
 
+use cortex_m::interrupt;
+use heapless::spsc::{Consumer, Producer, Queue};
+use heapless::{ArrayLength, Vec};
+
+use crate::hal::blocking;
 use crate::hal::serial;
+use crate::hal::spi::FullDuplex;
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::stm32::{UART4, UART5};
 use crate::stm32::{USART1, USART2, USART3};
 use nb;
 use void::Void;
 
-use crate::gpio::gpioa::{PA10, PA2, PA3, PA9};
+use crate::gpio::gpioa::{PA0, PA1, PA10, PA11, PA12, PA13, PA2, PA3, PA4, PA8, PA9};
 #[cfg(any(
     feature = "stm32f301",
     feature = "stm32f318",
@@ -19,9 +37,32 @@ use crate::gpio::gpioa::{PA10, PA2, PA3, PA9};
     feature = "stm32f358",
     feature = "stm32f398"
 ))]
-use crate::gpio::gpiob::PB11;
-use crate::gpio::gpiob::{PB10, PB6, PB7};
-use crate::gpio::gpioc::{PC10, PC11, PC4, PC5};
+use crate::gpio::gpioa::{PA14, PA15};
+use crate::gpio::gpiob::{PB10, PB14, PB3, PB4, PB5, PB6, PB7};
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpiob::{PB11, PB12, PB13};
+use crate::gpio::gpioc::{PC10, PC11, PC12, PC4, PC5};
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpiod::{PD10, PD11, PD12, PD3, PD4, PD7};
 #[cfg(any(
     feature = "stm32f302",
     feature = "stm32f303xb",
@@ -49,10 +90,21 @@ use crate::gpio::gpiod::{PD5, PD6, PD8, PD9};
     feature = "stm32f398"
 ))]
 use crate::gpio::gpioe::{PE0, PE1, PE15};
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::{gpiod::PD2, AF5};
 
 use crate::gpio::AF7;
 use crate::rcc::{Clocks, APB1, APB2};
-use crate::time::Bps;
+use crate::spi::{Mode, Phase, Polarity};
+use crate::time::{Bps, Hertz};
 
 /// Interrupt event
 pub enum Event {
@@ -60,6 +112,87 @@ pub enum Event {
     Rxne,
     /// New data can be sent
     Txe,
+    /// The receive line became idle after a frame
+    Idle,
+    /// The last frame was sent completely
+    TransmissionComplete,
+    /// The receive line stayed idle for the receiver timeout (see
+    /// `Serial::set_receiver_timeout`)
+    ReceiverTimeout,
+    /// The match character was received (see `Serial::set_match_character`)
+    CharacterMatch,
+    /// A frame with a parity error was received
+    ParityError,
+    /// A break was detected in LIN mode
+    LinBreak,
+}
+
+/// Length of a break, which is detected in LIN mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinBreakLength {
+    /// 10 bits
+    Bits10,
+    /// 11 bits
+    Bits11,
+}
+
+/// IrDA SIR mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IrdaMode {
+    /// Pulses of 3/16 bit duration
+    Normal,
+    /// Pulses of 3 periods of the low-power clock (about 1.8 MHz)
+    LowPower,
+}
+
+/// Smartcard (ISO 7816-3) configuration
+///
+/// Defaults to no extra guard time and three retries, if the card signals a parity error
+/// with a NACK.
+#[derive(Clone, Copy, Debug)]
+pub struct SmartcardConfig {
+    prescaler: u8,
+    guard_time: u8,
+    nack: bool,
+    retries: u8,
+}
+
+impl SmartcardConfig {
+    /// Creates a configuration, where the card clock is the peripheral clock divided by
+    /// 2 * `prescaler`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prescaler` is not in the range 1 to 31.
+    pub fn new(prescaler: u8) -> Self {
+        assert!(prescaler >= 1 && prescaler <= 31);
+
+        SmartcardConfig {
+            prescaler,
+            guard_time: 0,
+            nack: true,
+            retries: 3,
+        }
+    }
+
+    /// Sets the guard time in bit durations, after which a transmission is complete
+    pub fn guard_time(mut self, bits: u8) -> Self {
+        self.guard_time = bits;
+        self
+    }
+
+    /// Enables or disables sending a NACK on parity errors
+    pub fn nack(mut self, enable: bool) -> Self {
+        self.nack = enable;
+        self
+    }
+
+    /// Sets how often a frame is repeated after a NACK (at most 7)
+    pub fn retries(mut self, count: u8) -> Self {
+        assert!(count <= 7);
+        self.retries = count;
+        self
+    }
 }
 
 /// Serial error
@@ -84,6 +217,22 @@ pub unsafe trait TxPin<USART> {}
 /// RX pin - DO NOT IMPLEMENT THIS TRAIT
 pub unsafe trait RxPin<USART> {}
 
+/// RTS pin - DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait RtsPin<USART> {}
+
+/// CTS pin - DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait CtsPin<USART> {}
+
+/// RS-485 driver enable (DE) pin - DO NOT IMPLEMENT THIS TRAIT
+///
+/// The driver enable output shares its pins with RTS.
+pub unsafe trait DePin<USART> {}
+
+unsafe impl<USART, PIN: RtsPin<USART>> DePin<USART> for PIN {}
+
+/// CK pin - DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait CkPin<USART> {}
+
 unsafe impl TxPin<USART1> for PA9<AF7> {}
 unsafe impl TxPin<USART1> for PB6<AF7> {}
 unsafe impl TxPin<USART1> for PC4<AF7> {}
@@ -119,8 +268,18 @@ unsafe impl RxPin<USART1> for PC5<AF7> {}
 unsafe impl RxPin<USART1> for PE1<AF7> {}
 
 unsafe impl TxPin<USART2> for PA2<AF7> {}
-// unsafe impl TxPin<USART2> for PA14<AF7> {}
-// unsafe impl TxPin<USART2> for PB3<AF7> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl TxPin<USART2> for PA14<AF7> {}
+unsafe impl TxPin<USART2> for PB3<AF7> {}
 #[cfg(any(
     feature = "stm32f302",
     feature = "stm32f303xb",
@@ -137,8 +296,18 @@ unsafe impl TxPin<USART2> for PA2<AF7> {}
 unsafe impl TxPin<USART2> for PD5<AF7> {}
 
 unsafe impl RxPin<USART2> for PA3<AF7> {}
-// unsafe impl RxPin<USART2> for PA15<AF7> {}
-// unsafe impl RxPin<USART2> for PB4<AF7> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RxPin<USART2> for PA15<AF7> {}
+unsafe impl RxPin<USART2> for PB4<AF7> {}
 #[cfg(any(
     feature = "stm32f302",
     feature = "stm32f303xb",
@@ -211,6 +380,244 @@ unsafe impl RxPin<USART3> for PD9<AF7> {}
 ))]
 unsafe impl RxPin<USART3> for PE15<AF7> {}
 
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl TxPin<UART4> for PC10<AF5> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RxPin<UART4> for PC11<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl TxPin<UART5> for PC12<AF5> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RxPin<UART5> for PD2<AF5> {}
+
+unsafe impl RtsPin<USART1> for PA12<AF7> {}
+
+unsafe impl CtsPin<USART1> for PA11<AF7> {}
+
+unsafe impl RtsPin<USART2> for PA1<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RtsPin<USART2> for PD4<AF7> {}
+
+unsafe impl CtsPin<USART2> for PA0<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CtsPin<USART2> for PD3<AF7> {}
+
+unsafe impl RtsPin<USART3> for PB14<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl RtsPin<USART3> for PD12<AF7> {}
+
+unsafe impl CtsPin<USART3> for PA13<AF7> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CtsPin<USART3> for PB13<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CtsPin<USART3> for PD11<AF7> {}
+
+unsafe impl CkPin<USART1> for PA8<AF7> {}
+
+unsafe impl CkPin<USART2> for PA4<AF7> {}
+unsafe impl CkPin<USART2> for PB5<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CkPin<USART2> for PD7<AF7> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f318",
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f334",
+    feature = "stm32f328",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CkPin<USART3> for PB12<AF7> {}
+unsafe impl CkPin<USART3> for PC12<AF7> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl CkPin<USART3> for PD10<AF7> {}
+
+macro_rules! clear_errors {
+    ($w:ident) => {
+        $w.pecf()
+            .set_bit()
+            .fecf()
+            .set_bit()
+            .ncf()
+            .set_bit()
+            .orecf()
+            .set_bit()
+    };
+}
+
+macro_rules! events {
+    ($USARTX:ident) => {
+        /// Starts listening for an interrupt event
+        pub fn listen(&mut self, event: Event) {
+            Self::set_interrupt(event, true);
+        }
+
+        /// Stops listening for an interrupt event
+        pub fn unlisten(&mut self, event: Event) {
+            Self::set_interrupt(event, false);
+        }
+
+        fn set_interrupt(event: Event, enable: bool) {
+            // NOTE(unsafe) the control registers are shared between `Tx` and `Rx`, which may
+            // live in different contexts, so they are only modified in a critical section
+            interrupt::free(|_| {
+                let usart = unsafe { &*$USARTX::ptr() };
+
+                match event {
+                    Event::Rxne => usart.cr1.modify(|_, w| w.rxneie().bit(enable)),
+                    Event::Txe => usart.cr1.modify(|_, w| w.txeie().bit(enable)),
+                    Event::Idle => usart.cr1.modify(|_, w| w.idleie().bit(enable)),
+                    Event::TransmissionComplete => usart.cr1.modify(|_, w| w.tcie().bit(enable)),
+                    Event::ReceiverTimeout => usart.cr1.modify(|_, w| w.rtoie().bit(enable)),
+                    Event::CharacterMatch => usart.cr1.modify(|_, w| w.cmie().bit(enable)),
+                    Event::ParityError => usart.cr1.modify(|_, w| w.peie().bit(enable)),
+                    Event::LinBreak => usart.cr2.modify(|_, w| w.lbdie().bit(enable)),
+                }
+            })
+        }
+
+        /// Returns `true` if the flag of `event` is set
+        pub fn event_occurred(&self, event: Event) -> bool {
+            // NOTE(unsafe) atomic read with no side effects
+            let isr = unsafe { (*$USARTX::ptr()).isr.read() };
+
+            match event {
+                Event::Rxne => isr.rxne().bit_is_set(),
+                Event::Txe => isr.txe().bit_is_set(),
+                Event::Idle => isr.idle().bit_is_set(),
+                Event::TransmissionComplete => isr.tc().bit_is_set(),
+                Event::ReceiverTimeout => isr.rtof().bit_is_set(),
+                Event::CharacterMatch => isr.cmf().bit_is_set(),
+                Event::ParityError => isr.pe().bit_is_set(),
+                Event::LinBreak => isr.lbdf().bit_is_set(),
+            }
+        }
+
+        /// Clears the flag of `event`
+        ///
+        /// `Rxne` is cleared by discarding the received data. `Txe` is only cleared by
+        /// sending data, so this does nothing for it.
+        pub fn clear_event(&mut self, event: Event) {
+            // NOTE(unsafe) atomic write to stateless registers
+            let usart = unsafe { &*$USARTX::ptr() };
+
+            match event {
+                Event::Rxne => usart.rqr.write(|w| w.rxfrq().set_bit()),
+                Event::Txe => {}
+                Event::Idle => usart.icr.write(|w| w.idlecf().set_bit()),
+                Event::TransmissionComplete => usart.icr.write(|w| w.tccf().set_bit()),
+                Event::ReceiverTimeout => usart.icr.write(|w| w.rtocf().set_bit()),
+                Event::CharacterMatch => usart.icr.write(|w| w.cmcf().set_bit()),
+                Event::ParityError => usart.icr.write(|w| w.pecf().set_bit()),
+                Event::LinBreak => usart.icr.write(|w| w.lbdcf().set_bit()),
+            }
+        }
+    };
+}
+
 /// Serial abstraction
 pub struct Serial<USART, PINS> {
     usart: USART,
@@ -227,9 +634,183 @@ pub struct Tx<USART> {
     _usart: PhantomData<USART>,
 }
 
+/// Synchronous serial master, which outputs the clock on the CK pin
+///
+/// Frames are 8 bits long and sent MSB first, like on a SPI bus. The clock is generated
+/// for the data bits only, so a peripheral sees neither start nor stop bits.
+pub struct SyncSerial<USART, PINS> {
+    usart: USART,
+    pins: PINS,
+}
+
+/// The line did not fit into the buffer passed to `BufferedSerial::read_line`
+///
+/// The beginning of the line was copied into the buffer, the rest was discarded.
+#[derive(Debug)]
+pub struct LineTooLong;
+
+/// Interrupt driven serial interface with fixed-capacity ring buffers
+///
+/// `BufferedSerial::new` splits the interface into this application half and a
+/// `BufferedSerialIsr`, whose `on_interrupt` has to be called from the USART interrupt
+/// handler. It moves received bytes into the receive buffer and feeds the transmitter from
+/// the transmit buffer. The halves only share the lock-free single producer single
+/// consumer queues, so they can live in different contexts.
+///
+/// The buffer capacities are given as `heapless::consts`, e.g. `U64`. The queues are
+/// `'static`, e.g.
+///
+/// ``` ignore
+/// static mut RX: Queue<u8, U64> = Queue(heapless::i::Queue::new());
+/// ```
+pub struct BufferedSerial<USART, RXN, TXN>
+where
+    RXN: ArrayLength<u8>,
+    TXN: ArrayLength<u8>,
+{
+    rx_buffer: Consumer<'static, u8, RXN>,
+    tx_buffer: Producer<'static, u8, TXN>,
+    // received bytes of a line, which is not complete yet
+    line: Vec<u8, RXN>,
+    tx_overflows: u32,
+    _usart: PhantomData<USART>,
+}
+
+/// Interrupt half of a `BufferedSerial`, which owns the transmitter and the receiver
+pub struct BufferedSerialIsr<USART, RXN, TXN>
+where
+    RXN: ArrayLength<u8>,
+    TXN: ArrayLength<u8>,
+{
+    tx: Tx<USART>,
+    rx: Rx<USART>,
+    rx_buffer: Producer<'static, u8, RXN>,
+    tx_buffer: Consumer<'static, u8, TXN>,
+    rx_overflows: u32,
+    rx_errors: u32,
+}
+
+impl<USART, RXN, TXN> BufferedSerial<USART, RXN, TXN>
+where
+    RXN: ArrayLength<u8>,
+    TXN: ArrayLength<u8>,
+{
+    /// Takes the oldest byte from the receive buffer
+    pub fn read(&mut self) -> Option<u8> {
+        if self.line.is_empty() {
+            self.rx_buffer.dequeue()
+        } else {
+            // bytes of an incomplete line were received first
+            let (byte, len) = (self.line[0], self.line.len());
+            self.line.copy_within(1.., 0);
+            self.line.truncate(len - 1);
+            Some(byte)
+        }
+    }
+
+    /// Returns the number of bytes in the receive buffer
+    pub fn available(&self) -> usize {
+        self.line.len() + self.rx_buffer.len()
+    }
+
+    /// Returns the number of bytes in the transmit buffer, which are not sent yet
+    pub fn pending(&self) -> usize {
+        self.tx_buffer.len()
+    }
+
+    /// Reads the bytes up to `delimiter` into `buffer`, once the delimiter was received
+    ///
+    /// The delimiter is consumed, but not copied. Returns the number of bytes written to
+    /// `buffer`. If as many bytes as the receive buffer holds arrive before the
+    /// delimiter, they are taken as a line, which is too long.
+    pub fn read_until(
+        &mut self,
+        delimiter: u8,
+        buffer: &mut [u8],
+    ) -> nb::Result<usize, LineTooLong> {
+        let complete = loop {
+            if self.line.len() == self.line.capacity() {
+                break false;
+            }
+
+            match self.rx_buffer.dequeue() {
+                Some(byte) if byte == delimiter => break true,
+                // NOTE(unwrap) the line is not full
+                Some(byte) => self.line.push(byte).unwrap(),
+                None => return Err(nb::Error::WouldBlock),
+            }
+        };
+
+        let len = cmp::min(self.line.len(), buffer.len());
+        buffer[..len].copy_from_slice(&self.line[..len]);
+        let truncated = !complete || len < self.line.len();
+        self.line.clear();
+
+        if truncated {
+            Err(nb::Error::Other(LineTooLong))
+        } else {
+            Ok(len)
+        }
+    }
+
+    /// Reads a line terminated by `\n` into `buffer`, once it was received
+    ///
+    /// The terminator and a preceding `\r` are not copied. Returns the length of the line.
+    pub fn read_line(&mut self, buffer: &mut [u8]) -> nb::Result<usize, LineTooLong> {
+        let len = self.read_until(b'\n', buffer)?;
+
+        if len > 0 && buffer[len - 1] == b'\r' {
+            Ok(len - 1)
+        } else {
+            Ok(len)
+        }
+    }
+
+    /// Returns the number of bytes, which were dropped as the transmit buffer was full
+    pub fn tx_overflows(&self) -> u32 {
+        self.tx_overflows
+    }
+
+    /// Resets the transmit overflow counter
+    pub fn reset_counters(&mut self) {
+        self.tx_overflows = 0;
+    }
+}
+
+impl<USART, RXN, TXN> BufferedSerialIsr<USART, RXN, TXN>
+where
+    RXN: ArrayLength<u8>,
+    TXN: ArrayLength<u8>,
+{
+    /// Returns the number of received bytes, which were dropped as the receive buffer was
+    /// full
+    pub fn rx_overflows(&self) -> u32 {
+        self.rx_overflows
+    }
+
+    /// Returns the number of parity, framing, noise and overrun errors
+    pub fn rx_errors(&self) -> u32 {
+        self.rx_errors
+    }
+
+    /// Resets the receive overflow and error counters
+    pub fn reset_counters(&mut self) {
+        self.rx_overflows = 0;
+        self.rx_errors = 0;
+    }
+}
+
 macro_rules! hal {
     ($(
-        $USARTX:ident: ($usartX:ident, $APB:ident, $usartXen:ident, $usartXrst:ident, $pclkX:ident),
+        $USARTX:ident: (
+            $usartX:ident,
+            $usartX_lin:ident,
+            $usartX_irda:ident,
+            $APB:ident,
+            $usartXen:ident,
+            $usartXrst:ident,
+            $pclkX:ident
+        ),
     )+) => {
         $(
             impl<TX, RX> Serial<$USARTX, (TX, RX)> {
@@ -268,29 +849,176 @@ macro_rules! hal {
                     Serial { usart, pins }
                 }
 
-                /// Starts listening for an interrupt event
-                pub fn listen(&mut self, event: Event) {
-                    match event {
-                        Event::Rxne => {
-                            self.usart.cr1.modify(|_, w| w.rxneie().set_bit())
-                        },
-                        Event::Txe => {
-                            self.usart.cr1.modify(|_, w| w.txeie().set_bit())
-                        },
+                /// Configures a USART peripheral for LIN communication
+                ///
+                /// Breaks are sent with `send_break` and detected as `Event::LinBreak`.
+                pub fn $usartX_lin(
+                    usart: $USARTX,
+                    pins: (TX, RX),
+                    baud_rate: Bps,
+                    break_length: LinBreakLength,
+                    clocks: Clocks,
+                    apb: &mut $APB,
+                ) -> Self
+                where
+                    TX: TxPin<$USARTX>,
+                    RX: RxPin<$USARTX>,
+                {
+                    let serial = Self::$usartX(usart, pins, baud_rate, clocks, apb);
+
+                    // LINEN and LBDL can only be written while the USART is disabled
+                    serial.usart.cr1.modify(|_, w| w.ue().clear_bit());
+                    serial.usart.cr2.modify(|_, w| {
+                        w.linen()
+                            .set_bit()
+                            .lbdl()
+                            .bit(break_length == LinBreakLength::Bits11)
+                    });
+                    serial.usart.cr1.modify(|_, w| w.ue().set_bit());
+
+                    serial
+                }
+
+                /// Configures a USART peripheral for IrDA SIR communication
+                ///
+                /// The pins have to be connected to an infrared transceiver.
+                pub fn $usartX_irda(
+                    usart: $USARTX,
+                    pins: (TX, RX),
+                    baud_rate: Bps,
+                    mode: IrdaMode,
+                    clocks: Clocks,
+                    apb: &mut $APB,
+                ) -> Self
+                where
+                    TX: TxPin<$USARTX>,
+                    RX: RxPin<$USARTX>,
+                {
+                    let serial = Self::$usartX(usart, pins, baud_rate, clocks, apb);
+
+                    // PSC: divides the peripheral clock down to the low-power frequency,
+                    //      which has to be between 1.42 and 2.12 MHz, or 1 in normal mode
+                    let psc = match mode {
+                        IrdaMode::Normal => 1,
+                        IrdaMode::LowPower => {
+                            cmp::min(cmp::max(clocks.$pclkX().0 / 1_843_200, 1), 255)
+                        }
+                    };
+
+                    // IREN, IRLP and PSC can only be written while the USART is disabled
+                    serial.usart.cr1.modify(|_, w| w.ue().clear_bit());
+                    serial.usart.gtpr.modify(|_, w| unsafe { w.psc().bits(psc as u8) });
+                    serial.usart.cr3.modify(|_, w| {
+                        w.iren()
+                            .set_bit()
+                            .irlp()
+                            .bit(mode == IrdaMode::LowPower)
+                    });
+                    serial.usart.cr1.modify(|_, w| w.ue().set_bit());
+
+                    serial
+                }
+
+                /// Enables hardware flow control
+                ///
+                /// The transmitter only starts a new frame while CTS is asserted. RTS is
+                /// deasserted while the receiver's data register is full.
+                pub fn flow_control<RTS, CTS>(
+                    self,
+                    rts: RTS,
+                    cts: CTS,
+                ) -> Serial<$USARTX, (TX, RX, RTS, CTS)>
+                where
+                    RTS: RtsPin<$USARTX>,
+                    CTS: CtsPin<$USARTX>,
+                {
+                    // RTSE and CTSE can only be written while the USART is disabled
+                    self.usart.cr1.modify(|_, w| w.ue().clear_bit());
+                    self.usart.cr3.modify(|_, w| w.rtse().set_bit().ctse().set_bit());
+                    self.usart.cr1.modify(|_, w| w.ue().set_bit());
+
+                    let (tx, rx) = self.pins;
+                    Serial {
+                        usart: self.usart,
+                        pins: (tx, rx, rts, cts),
                     }
                 }
 
-                /// Starts listening for an interrupt event
-                pub fn unlisten(&mut self, event: Event) {
-                    match event {
-                        Event::Rxne => {
-                            self.usart.cr1.modify(|_, w| w.rxneie().clear_bit())
-                        },
-                        Event::Txe => {
-                            self.usart.cr1.modify(|_, w| w.txeie().clear_bit())
-                        },
+                /// Enables the RS-485 driver enable output
+                ///
+                /// DE is asserted `assertion_time` before the start bit of the first frame
+                /// and deasserted `deassertion_time` after the stop bit of the last frame.
+                /// Both times are given in sample times, i.e. 1/16 of a bit, and must be
+                /// lower than 32.
+                pub fn rs485<DE>(
+                    self,
+                    de: DE,
+                    assertion_time: u8,
+                    deassertion_time: u8,
+                ) -> Serial<$USARTX, (TX, RX, DE)>
+                where
+                    DE: DePin<$USARTX>,
+                {
+                    assert!(assertion_time < 32 && deassertion_time < 32);
+
+                    // DEAT, DEDT and DEM can only be written while the USART is disabled
+                    self.usart.cr1.modify(|_, w| w.ue().clear_bit());
+                    // NOTE(unsafe) the times are checked to fit into the 5-bit fields
+                    self.usart.cr1.modify(|_, w| unsafe {
+                        w.deat().bits(assertion_time).dedt().bits(deassertion_time)
+                    });
+                    // DEM: driver enable mode
+                    // DEP: DE is active high
+                    self.usart.cr3.modify(|_, w| w.dem().set_bit().dep().clear_bit());
+                    self.usart.cr1.modify(|_, w| w.ue().set_bit());
+
+                    let (tx, rx) = self.pins;
+                    Serial {
+                        usart: self.usart,
+                        pins: (tx, rx, de),
                     }
                 }
+            }
+
+            impl<PINS> Serial<$USARTX, PINS> {
+                events!($USARTX);
+
+                /// Sets the receiver timeout in bit durations, or disables it with `None`
+                ///
+                /// The timeout starts at the end of each received frame and raises
+                /// `Event::ReceiverTimeout`, if no new frame arrives in time.
+                pub fn set_receiver_timeout(&mut self, bits: Option<u32>) {
+                    if let Some(bits) = bits {
+                        assert!(bits < 1 << 24);
+                        // NOTE(unsafe) the timeout is checked to fit into the 24-bit field
+                        self.usart.rtor.modify(|_, w| unsafe { w.rto().bits(bits) });
+                    }
+
+                    self.usart.cr2.modify(|_, w| w.rtoen().bit(bits.is_some()));
+                }
+
+                /// Sets the character, which raises `Event::CharacterMatch` when received
+                pub fn set_match_character(&mut self, character: u8) {
+                    // ADD can only be written while the receiver is disabled
+                    self.usart.cr1.modify(|_, w| w.re().clear_bit());
+                    // NOTE(unsafe) ADD occupies the bits 24 to 31
+                    self.usart.cr2.modify(|r, w| unsafe {
+                        w.bits((r.bits() & 0x00ff_ffff) | (u32::from(character) << 24))
+                    });
+                    self.usart.cr1.modify(|_, w| w.re().set_bit());
+                }
+
+                /// Clears all pending parity, framing, noise and overrun errors
+                ///
+                /// The received data is kept.
+                pub fn clear_errors(&mut self) {
+                    self.usart.icr.write(|w| clear_errors!(w));
+                }
+
+                /// Sends a break character after the current frame
+                pub fn send_break(&mut self) {
+                    self.usart.rqr.write(|w| w.sbkrq().set_bit());
+                }
 
                 /// Splits the `Serial` abstraction into a transmitter and a receiver half
                 pub fn split(self) -> (Tx<$USARTX>, Rx<$USARTX>) {
@@ -305,25 +1033,55 @@ macro_rules! hal {
                 }
 
                 /// Releases the USART peripheral and associated pins
-                pub fn free(self) -> ($USARTX, (TX, RX)) {
+                pub fn free(self) -> ($USARTX, PINS) {
                     (self.usart, self.pins)
                 }
             }
 
+            impl Rx<$USARTX> {
+                events!($USARTX);
+
+                /// Clears all pending parity, framing, noise and overrun errors
+                ///
+                /// The received data is kept.
+                pub fn clear_errors(&mut self) {
+                    // NOTE(unsafe) atomic write to stateless register
+                    unsafe { (*$USARTX::ptr()).icr.write(|w| clear_errors!(w)) }
+                }
+            }
+
+            impl Tx<$USARTX> {
+                events!($USARTX);
+
+                /// Sends a break character after the current frame
+                pub fn send_break(&mut self) {
+                    // NOTE(unsafe) atomic write to stateless register
+                    unsafe { (*$USARTX::ptr()).rqr.write(|w| w.sbkrq().set_bit()) }
+                }
+            }
+
             impl serial::Read<u8> for Rx<$USARTX> {
                 type Error = Error;
 
                 fn read(&mut self) -> nb::Result<u8, Error> {
                     // NOTE(unsafe) atomic read with no side effects
                     let isr = unsafe { (*$USARTX::ptr()).isr.read() };
+                    // NOTE(unsafe) atomic write to stateless register
+                    let icr = unsafe { &(*$USARTX::ptr()).icr };
 
+                    // The error flags are cleared one by one as they are reported. The
+                    // received data stays in RDR, so it is returned by the next call.
                     Err(if isr.pe().bit_is_set() {
+                        icr.write(|w| w.pecf().set_bit());
                         nb::Error::Other(Error::Parity)
                     } else if isr.fe().bit_is_set() {
+                        icr.write(|w| w.fecf().set_bit());
                         nb::Error::Other(Error::Framing)
                     } else if isr.nf().bit_is_set() {
+                        icr.write(|w| w.ncf().set_bit());
                         nb::Error::Other(Error::Noise)
                     } else if isr.ore().bit_is_set() {
+                        icr.write(|w| w.orecf().set_bit());
                         nb::Error::Other(Error::Overrun)
                     } else if isr.rxne().bit_is_set() {
                         // NOTE(read_volatile) see `write_volatile` below
@@ -338,11 +1096,14 @@ macro_rules! hal {
 
             impl serial::Write<u8> for Tx<$USARTX> {
                 // NOTE(Void) See section "29.7 USART interrupts"; the only possible errors during
-                // transmission are: clear to send (which is disabled in this case) errors and
-                // framing errors (which only occur in SmartCard mode); neither of these apply to
-                // our hardware configuration
+                // transmission are: clear to send errors (with hardware flow control, CTS only
+                // delays the transmission) and framing errors (which only occur in SmartCard
+                // mode, where they are reported by the receiver as the card's NACK is received
+                // on the same line); neither of these apply to the transmitter
                 type Error = Void;
 
+                /// Waits for the transmission complete flag, i.e. until the last frame was
+                /// shifted out, not just until the data register is empty
                 fn flush(&mut self) -> nb::Result<(), Void> {
                     // NOTE(unsafe) atomic read with no side effects
                     let isr = unsafe { (*$USARTX::ptr()).isr.read() };
@@ -370,28 +1131,391 @@ macro_rules! hal {
                     }
                 }
             }
+
+            // `bflush` waits for the transmission complete flag, so the last frame has left
+            // the shift register, once it returns
+            impl blocking::serial::write::Default<u8> for Tx<$USARTX> {}
+
+            impl fmt::Write for Tx<$USARTX> {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    blocking::serial::Write::bwrite_all(self, s.as_bytes())
+                        .map_err(|_| fmt::Error)
+                }
+            }
+
+            impl<PINS> serial::Read<u8> for Serial<$USARTX, PINS> {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<u8, Error> {
+                    let mut rx: Rx<$USARTX> = Rx { _usart: PhantomData };
+                    serial::Read::read(&mut rx)
+                }
+            }
+
+            impl<PINS> serial::Write<u8> for Serial<$USARTX, PINS> {
+                type Error = Void;
+
+                fn flush(&mut self) -> nb::Result<(), Void> {
+                    let mut tx: Tx<$USARTX> = Tx { _usart: PhantomData };
+                    serial::Write::flush(&mut tx)
+                }
+
+                fn write(&mut self, byte: u8) -> nb::Result<(), Void> {
+                    let mut tx: Tx<$USARTX> = Tx { _usart: PhantomData };
+                    serial::Write::write(&mut tx, byte)
+                }
+            }
+
+            impl<PINS> blocking::serial::write::Default<u8> for Serial<$USARTX, PINS> {}
+
+            impl<PINS> fmt::Write for Serial<$USARTX, PINS> {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    blocking::serial::Write::bwrite_all(self, s.as_bytes())
+                        .map_err(|_| fmt::Error)
+                }
+            }
+
+            impl<RXN, TXN> BufferedSerial<$USARTX, RXN, TXN>
+            where
+                RXN: ArrayLength<u8>,
+                TXN: ArrayLength<u8>,
+            {
+                /// Creates a buffered serial interface over the `'static` queues and starts
+                /// listening for received data
+                ///
+                /// Returns the application half and the interrupt half.
+                pub fn new(
+                    tx: Tx<$USARTX>,
+                    mut rx: Rx<$USARTX>,
+                    rx_queue: &'static mut Queue<u8, RXN>,
+                    tx_queue: &'static mut Queue<u8, TXN>,
+                ) -> (Self, BufferedSerialIsr<$USARTX, RXN, TXN>) {
+                    let (rx_producer, rx_consumer) = rx_queue.split();
+                    let (tx_producer, tx_consumer) = tx_queue.split();
+                    rx.listen(Event::Rxne);
+
+                    (
+                        BufferedSerial {
+                            rx_buffer: rx_consumer,
+                            tx_buffer: tx_producer,
+                            line: Vec::new(),
+                            tx_overflows: 0,
+                            _usart: PhantomData,
+                        },
+                        BufferedSerialIsr {
+                            tx,
+                            rx,
+                            rx_buffer: rx_producer,
+                            tx_buffer: tx_consumer,
+                            rx_overflows: 0,
+                            rx_errors: 0,
+                        },
+                    )
+                }
+
+                /// Queues `data` for transmission and returns the number of queued bytes
+                ///
+                /// Bytes, which don't fit into the transmit buffer, are dropped and counted.
+                pub fn write(&mut self, data: &[u8]) -> usize {
+                    let mut queued = 0;
+                    for &byte in data {
+                        if self.tx_buffer.enqueue(byte).is_ok() {
+                            queued += 1;
+                        } else {
+                            self.tx_overflows = self.tx_overflows.wrapping_add(1);
+                        }
+                    }
+
+                    if queued > 0 {
+                        // the interrupt half stops listening once the buffer ran empty
+                        Tx::<$USARTX>::set_interrupt(Event::Txe, true);
+                    }
+
+                    queued
+                }
+            }
+
+            impl<RXN, TXN> BufferedSerialIsr<$USARTX, RXN, TXN>
+            where
+                RXN: ArrayLength<u8>,
+                TXN: ArrayLength<u8>,
+            {
+                /// Handles the USART interrupt
+                pub fn on_interrupt(&mut self) {
+                    loop {
+                        match serial::Read::read(&mut self.rx) {
+                            Ok(byte) => {
+                                if self.rx_buffer.enqueue(byte).is_err() {
+                                    self.rx_overflows = self.rx_overflows.wrapping_add(1);
+                                }
+                            }
+                            Err(nb::Error::Other(_)) => {
+                                self.rx_errors = self.rx_errors.wrapping_add(1);
+                            }
+                            Err(nb::Error::WouldBlock) => break,
+                        }
+                    }
+
+                    while self.tx.event_occurred(Event::Txe) {
+                        match self.tx_buffer.dequeue() {
+                            // NOTE(unwrap) the transmit data register is empty
+                            Some(byte) => serial::Write::write(&mut self.tx, byte).unwrap(),
+                            None => {
+                                self.tx.unlisten(Event::Txe);
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                /// Stops listening for interrupts and releases the transmitter and receiver
+                ///
+                /// The buffered data is left in the queues.
+                pub fn free(mut self) -> (Tx<$USARTX>, Rx<$USARTX>) {
+                    self.rx.unlisten(Event::Rxne);
+                    self.tx.unlisten(Event::Txe);
+
+                    (self.tx, self.rx)
+                }
+            }
+
+            impl<RXN, TXN> fmt::Write for BufferedSerial<$USARTX, RXN, TXN>
+            where
+                RXN: ArrayLength<u8>,
+                TXN: ArrayLength<u8>,
+            {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    if self.write(s.as_bytes()) == s.len() {
+                        Ok(())
+                    } else {
+                        Err(fmt::Error)
+                    }
+                }
+            }
+        )+
+    }
+}
+
+/// Implements the modes, which use the CK pin
+macro_rules! synchronous {
+    ($(
+        $USARTX:ident: (
+            $usartX:ident,
+            $usartX_smartcard:ident,
+            $APB:ident,
+            $usartXen:ident,
+            $usartXrst:ident,
+            $pclkX:ident
+        ),
+    )+) => {
+        $(
+            impl<TX, CK> Serial<$USARTX, (TX, CK)> {
+                /// Configures a USART peripheral to communicate with a smartcard
+                ///
+                /// The smartcard's I/O line is connected to the TX pin, which has to be
+                /// configured as open drain output. The card clock is output on CK.
+                pub fn $usartX_smartcard(
+                    usart: $USARTX,
+                    pins: (TX, CK),
+                    baud_rate: Bps,
+                    config: SmartcardConfig,
+                    clocks: Clocks,
+                    apb: &mut $APB,
+                ) -> Self
+                where
+                    TX: TxPin<$USARTX>,
+                    CK: CkPin<$USARTX>,
+                {
+                    // enable or reset $USARTX
+                    apb.enr().modify(|_, w| w.$usartXen().set_bit());
+                    apb.rstr().modify(|_, w| w.$usartXrst().set_bit());
+                    apb.rstr().modify(|_, w| w.$usartXrst().clear_bit());
+
+                    let brr = clocks.$pclkX().0 / baud_rate.0;
+                    assert!(brr >= 16, "impossible baud rate");
+                    usart.brr.write(|w| unsafe { w.bits(brr) });
+
+                    // GT: guard time
+                    // PSC: card clock prescaler
+                    // NOTE(unsafe) the prescaler is checked by `SmartcardConfig`
+                    usart.gtpr.write(|w| unsafe {
+                        w.gt().bits(config.guard_time).psc().bits(config.prescaler)
+                    });
+
+                    // SCEN: smartcard mode
+                    // NACK: send a NACK on parity errors
+                    // SCARCNT: automatic retries
+                    usart.cr3.write(|w| unsafe {
+                        w.scen()
+                            .set_bit()
+                            .nack()
+                            .bit(config.nack)
+                            .scarcnt()
+                            .bits(config.retries)
+                    });
+
+                    // STOP: 1.5 stop bits
+                    // CLKEN: output the card clock
+                    usart
+                        .cr2
+                        .write(|w| unsafe { w.stop().bits(0b11).clken().set_bit() });
+
+                    // M: 9-bit frames, the 9th bit being the parity
+                    // PCE: even parity
+                    // UE: enable USART
+                    // RE: enable receiver
+                    // TE: enable transceiver
+                    usart.cr1.write(|w| {
+                        w.m()
+                            .set_bit()
+                            .pce()
+                            .set_bit()
+                            .ue()
+                            .set_bit()
+                            .re()
+                            .set_bit()
+                            .te()
+                            .set_bit()
+                    });
+
+                    Serial { usart, pins }
+                }
+            }
+
+            impl<TX, RX, CK> SyncSerial<$USARTX, (TX, RX, CK)> {
+                /// Configures a USART peripheral as synchronous master
+                ///
+                /// The clock frequency is at most 1/16 of the peripheral clock.
+                pub fn $usartX<F>(
+                    usart: $USARTX,
+                    pins: (TX, RX, CK),
+                    mode: Mode,
+                    freq: F,
+                    clocks: Clocks,
+                    apb: &mut $APB,
+                ) -> Self
+                where
+                    F: Into<Hertz>,
+                    TX: TxPin<$USARTX>,
+                    RX: RxPin<$USARTX>,
+                    CK: CkPin<$USARTX>,
+                {
+                    // enable or reset $USARTX
+                    apb.enr().modify(|_, w| w.$usartXen().set_bit());
+                    apb.rstr().modify(|_, w| w.$usartXrst().set_bit());
+                    apb.rstr().modify(|_, w| w.$usartXrst().clear_bit());
+
+                    let brr = clocks.$pclkX().0 / freq.into().0;
+                    assert!(brr >= 16, "impossible clock frequency");
+                    usart.brr.write(|w| unsafe { w.bits(brr) });
+
+                    // CLKEN: output the clock
+                    // CPOL: clock polarity
+                    // CPHA: clock phase
+                    // LBCL: the clock pulse of the last data bit is output as well
+                    // MSBFIRST: most significant bit first, like SPI
+                    // STOP: 1 stop bit
+                    usart.cr2.write(|w| unsafe {
+                        w.clken()
+                            .set_bit()
+                            .cpol()
+                            .bit(mode.polarity == Polarity::IdleHigh)
+                            .cpha()
+                            .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                            .lbcl()
+                            .set_bit()
+                            .msbfirst()
+                            .set_bit()
+                            .stop()
+                            .bits(0b00)
+                    });
+
+                    // UE: enable USART
+                    // RE: enable receiver
+                    // TE: enable transceiver
+                    usart
+                        .cr1
+                        .write(|w| w.ue().set_bit().re().set_bit().te().set_bit());
+
+                    SyncSerial { usart, pins }
+                }
+
+                /// Enables or disables the clock pulse of the last data bit
+                ///
+                /// It is enabled by default, as SPI peripherals expect a clock pulse for
+                /// every bit.
+                pub fn last_bit_clock(&mut self, enable: bool) {
+                    // LBCL can only be written while the USART is disabled
+                    self.usart.cr1.modify(|_, w| w.ue().clear_bit());
+                    self.usart.cr2.modify(|_, w| w.lbcl().bit(enable));
+                    self.usart.cr1.modify(|_, w| w.ue().set_bit());
+                }
+
+                /// Releases the USART peripheral and associated pins
+                pub fn free(self) -> ($USARTX, (TX, RX, CK)) {
+                    (self.usart, self.pins)
+                }
+            }
+
+            impl<PINS> FullDuplex<u8> for SyncSerial<$USARTX, PINS> {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<u8, Error> {
+                    let isr = self.usart.isr.read();
+
+                    // framing, noise and parity errors don't occur, as the master generates
+                    // the clock
+                    if isr.ore().bit_is_set() {
+                        self.usart.icr.write(|w| w.orecf().set_bit());
+                        Err(nb::Error::Other(Error::Overrun))
+                    } else if isr.rxne().bit_is_set() {
+                        // NOTE(read_volatile) see `write_volatile` below
+                        Ok(unsafe { ptr::read_volatile(&self.usart.rdr as *const _ as *const _) })
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+                    if self.usart.isr.read().txe().bit_is_set() {
+                        // NOTE(write_volatile) 8-bit write that's not possible through the svd2rust API
+                        unsafe { ptr::write_volatile(&self.usart.tdr as *const _ as *mut _, byte) }
+                        Ok(())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+            }
+
+            impl<PINS> blocking::spi::transfer::Default<u8> for SyncSerial<$USARTX, PINS> {}
+
+            impl<PINS> blocking::spi::write::Default<u8> for SyncSerial<$USARTX, PINS> {}
         )+
     }
 }
 
+hal! {
+    USART1: (usart1, usart1_lin, usart1_irda, APB2, usart1en, usart1rst, pclk2),
+    USART2: (usart2, usart2_lin, usart2_irda, APB1, usart2en, usart2rst, pclk1),
+    USART3: (usart3, usart3_lin, usart3_irda, APB1, usart3en, usart3rst, pclk1),
+}
+
+synchronous! {
+    USART1: (usart1, usart1_smartcard, APB2, usart1en, usart1rst, pclk2),
+    USART2: (usart2, usart2_smartcard, APB1, usart2en, usart2rst, pclk1),
+    USART3: (usart3, usart3_smartcard, APB1, usart3en, usart3rst, pclk1),
+}
+
 #[cfg(any(
-    feature = "stm32f301",
-    feature = "stm32f318",
-    feature = "stm32f303",
-    feature = "stm32f373",
-    feature = "stm32f378",
-    feature = "stm32f328",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
     feature = "stm32f358",
     feature = "stm32f398"
 ))]
 hal! {
-    USART1: (usart1, APB2, usart1en, usart1rst, pclk2),
-    USART2: (usart2, APB1, usart2en, usart2rst, pclk1),
-    USART3: (usart3, APB1, usart3en, usart3rst, pclk1),
-}
-
-#[cfg(any(feature = "stm32f302", feature = "stm32f334"))]
-hal! {
-    USART1: (usart1, APB2, usart1en, usart1rst, pclk2),
-    USART2: (usart2, APB1, usart2en, usart2rst, pclk1),
+    UART4: (uart4, uart4_lin, uart4_irda, APB1, uart4en, uart4rst, pclk1),
+    UART5: (uart5, uart5_lin, uart5_irda, APB1, uart5en, uart5rst, pclk1),
 }
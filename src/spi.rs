@@ -1,13 +1,16 @@
 //! Serial Peripheral Interface (SPI) bus
 
+use core::marker::PhantomData;
 use core::ptr;
 
+use crate::dma::{self, dma1};
 use crate::hal::spi::FullDuplex;
 pub use crate::hal::spi::{Mode, Phase, Polarity};
 use crate::stm32::{SPI1, SPI2, SPI3};
 use nb;
+use void::Void;
 
-use crate::gpio::gpioa::{PA5, PA6, PA7};
+use crate::gpio::gpioa::{PA15, PA4, PA5, PA6, PA7};
 #[cfg(any(
     feature = "stm32f301",
     feature = "stm32f302",
@@ -20,6 +23,18 @@ use crate::gpio::gpioa::{PA5, PA6, PA7};
     feature = "stm32f398"
 ))]
 use crate::gpio::gpiob::PB13;
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+use crate::gpio::gpiob::PB12;
 use crate::gpio::gpiob::{PB14, PB15, PB5};
 use crate::gpio::gpioc::{PC10, PC11, PC12};
 use crate::gpio::{AF5, AF6};
@@ -108,26 +123,543 @@ unsafe impl MosiPin<SPI2> for PB15<AF5> {}
 unsafe impl MosiPin<SPI3> for PB5<AF6> {}
 unsafe impl MosiPin<SPI3> for PC12<AF6> {}
 
+/// Bit order of a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitOrder {
+    /// Most significant bit first
+    MsbFirst,
+    /// Least significant bit first
+    LsbFirst,
+}
+
+/// Usage of the data lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Communication {
+    /// Full duplex on MISO and MOSI
+    FullDuplex,
+    /// Half duplex on a single bidirectional data line (MOSI for a master, MISO for a
+    /// slave), the direction is switched with `set_direction`
+    ///
+    /// A master starts out transmitting, so it does not generate the clock right away. A
+    /// slave starts out receiving, so it does not drive the line against the master.
+    HalfDuplex,
+    /// Simplex, only receiving
+    ///
+    /// A master generates the clock as long as it is enabled, so it starts out disabled.
+    ReceiveOnly,
+}
+
+/// Direction of the data line in half duplex communication
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Output, frames written to the SPI are sent
+    Transmit,
+    /// Input, a master generates the clock continuously
+    Receive,
+}
+
+/// SPI configuration
+///
+/// A `Mode` converts into a configuration with 8-bit frames, which are sent MSB first
+/// in full duplex without CRC.
+#[derive(Clone, Copy)]
+pub struct Config {
+    mode: Mode,
+    frame_size: u8,
+    bit_order: BitOrder,
+    crc_polynomial: Option<u16>,
+    communication: Communication,
+}
+
+impl Config {
+    /// Creates a configuration with 8-bit frames, which are sent MSB first in full duplex
+    /// without CRC
+    pub fn new(mode: Mode) -> Self {
+        Config {
+            mode,
+            frame_size: 8,
+            bit_order: BitOrder::MsbFirst,
+            crc_polynomial: None,
+            communication: Communication::FullDuplex,
+        }
+    }
+
+    /// Sets the number of bits per frame
+    ///
+    /// Frames of up to 8 bits are exchanged as `u8`, longer frames as `u16` words. The
+    /// word type is chosen with the `WORD` parameter of `Spi` and `SpiSlave`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is not in `4..=16`.
+    pub fn frame_size(mut self, bits: u8) -> Self {
+        assert!(bits >= 4 && bits <= 16, "invalid SPI frame size");
+        self.frame_size = bits;
+        self
+    }
+
+    /// Sets the bit order of a frame
+    pub fn bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+
+    /// Enables the hardware CRC calculation with the given polynomial
+    ///
+    /// The CRC is 8 bits long for frames of up to 8 bits and 16 bits long otherwise.
+    /// The reset value of the polynomial register is `0x7`.
+    pub fn crc(mut self, polynomial: u16) -> Self {
+        self.crc_polynomial = Some(polynomial);
+        self
+    }
+
+    /// Sets the usage of the data lines
+    pub fn communication(mut self, communication: Communication) -> Self {
+        self.communication = communication;
+        self
+    }
+}
+
+impl From<Mode> for Config {
+    fn from(mode: Mode) -> Self {
+        Config::new(mode)
+    }
+}
+
+/// Unused MISO pin (type state), for half duplex or transmit only communication
+pub struct NoMiso;
+
+/// Unused MOSI pin (type state), for receive only communication
+pub struct NoMosi;
+
+unsafe impl<SPI> MisoPin<SPI> for NoMiso {}
+
+unsafe impl<SPI> MosiPin<SPI> for NoMosi {}
+
+/// Software slave management (type state)
+///
+/// Used in place of a NSS pin, the slave is then selected with `SpiSlave::select`.
+pub struct SoftwareNss;
+
+// FIXME these should be "closed" traits
+/// NSS pin -- DO NOT IMPLEMENT THIS TRAIT
+pub unsafe trait NssPin<SPI> {
+    #[doc(hidden)]
+    const SOFTWARE: bool = false;
+}
+
+unsafe impl<SPI> NssPin<SPI> for SoftwareNss {
+    const SOFTWARE: bool = true;
+}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f328",
+    feature = "stm32f334",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI1> for PA4<AF5> {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303",
+    feature = "stm32f328",
+    feature = "stm32f334",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI1> for PA15<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI2> for PB12<AF5> {}
+
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI3> for PA4<AF6> {}
+#[cfg(any(
+    feature = "stm32f301",
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f318",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398"
+))]
+unsafe impl NssPin<SPI3> for PA15<AF6> {}
+
+/// Interrupt event
+pub enum Event {
+    /// New data has been received
+    Rxne,
+    /// New data can be sent
+    Txe,
+    /// An overrun, mode fault or CRC error occurred
+    Error,
+}
+
 /// SPI peripheral operating in full duplex master mode
-pub struct Spi<SPI, PINS> {
+///
+/// `WORD` is `u8` for frames of up to 8 bits and `u16` for longer ones.
+pub struct Spi<SPI, PINS, WORD = u8> {
     spi: SPI,
     pins: PINS,
+    _word: PhantomData<WORD>,
+}
+
+/// SPI master together with the DMA channel feeding its transmit FIFO
+///
+/// This is the DMA target of a full duplex transfer started with `Spi::read_dma` or
+/// `Spi::transfer_dma`.
+pub struct TxDma<SPI, PINS, WORD, C> {
+    spi: Spi<SPI, PINS, WORD>,
+    channel: C,
+}
+
+impl<SPI, PINS, WORD, C> TxDma<SPI, PINS, WORD, C> {
+    /// Releases the SPI and the transmit DMA channel
+    pub fn free(self) -> (Spi<SPI, PINS, WORD>, C) {
+        (self.spi, self.channel)
+    }
+}
+
+/// Word exchanged with the SPI, `u8` for frames of up to 8 bits and `u16` for longer ones
+pub trait Word: dma::Word {}
+
+impl Word for u8 {}
+
+impl Word for u16 {}
+
+/// SPI peripheral operating in full duplex slave mode
+///
+/// The master provides the clock, so data to be sent has to be in the transmit FIFO
+/// before the master starts a transfer, see `SpiSlave::preload`. `WORD` is `u8` for
+/// frames of up to 8 bits and `u16` for longer ones.
+pub struct SpiSlave<SPI, PINS, WORD = u8> {
+    spi: SPI,
+    pins: PINS,
+    _word: PhantomData<WORD>,
+}
+
+/// Implements the data register access shared by master and slave mode
+macro_rules! full_duplex {
+    ($SPI:ident, $SPIX:ident) => {
+        impl<PINS, WORD: Word> $SPI<$SPIX, PINS, WORD> {
+            /// Starts listening for an interrupt event
+            pub fn listen(&mut self, event: Event) {
+                match event {
+                    Event::Rxne => self.spi.cr2.modify(|_, w| w.rxneie().set_bit()),
+                    Event::Txe => self.spi.cr2.modify(|_, w| w.txeie().set_bit()),
+                    Event::Error => self.spi.cr2.modify(|_, w| w.errie().set_bit()),
+                }
+            }
+
+            /// Stops listening for an interrupt event
+            pub fn unlisten(&mut self, event: Event) {
+                match event {
+                    Event::Rxne => self.spi.cr2.modify(|_, w| w.rxneie().clear_bit()),
+                    Event::Txe => self.spi.cr2.modify(|_, w| w.txeie().clear_bit()),
+                    Event::Error => self.spi.cr2.modify(|_, w| w.errie().clear_bit()),
+                }
+            }
+
+            /// Switches the direction of the data line in half duplex communication
+            pub fn set_direction(&mut self, direction: Direction) {
+                self.spi
+                    .cr1
+                    .modify(|_, w| w.bidioe().bit(direction == Direction::Transmit));
+            }
+
+            /// Enables the SPI
+            pub fn enable(&mut self) {
+                self.spi.cr1.modify(|_, w| w.spe().enabled());
+            }
+
+            /// Disables the SPI
+            ///
+            /// In receive only communication, this stops the clock of a master.
+            pub fn disable(&mut self) {
+                self.spi.cr1.modify(|_, w| w.spe().disabled());
+            }
+
+            /// Transfers the CRC after the frame, which was just written to the SPI
+            ///
+            /// This has to be called right after the last data frame was sent.
+            pub fn send_crc(&mut self) {
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+
+            /// Resets the CRC calculation of both directions
+            pub fn reset_crc(&mut self) {
+                let spe = self.spi.cr1.read().spe().bit_is_set();
+                self.spi.cr1.modify(|_, w| w.spe().disabled());
+                self.spi.cr1.modify(|_, w| w.crcen().clear_bit());
+                self.spi.cr1.modify(|_, w| w.crcen().set_bit());
+                if spe {
+                    self.spi.cr1.modify(|_, w| w.spe().enabled());
+                }
+            }
+
+            /// Returns the CRC calculated over the sent frames
+            pub fn tx_crc(&self) -> u16 {
+                self.spi.txcrcr.read().bits() as u16
+            }
+
+            /// Returns the CRC calculated over the received frames
+            pub fn rx_crc(&self) -> u16 {
+                self.spi.rxcrcr.read().bits() as u16
+            }
+
+            /// Exchanges `words` followed by their CRC and checks the received CRC
+            ///
+            /// The received frames replace the content of `words`. The CRC is as long as
+            /// `WORD`.
+            pub fn transfer_with_crc<'w>(
+                &mut self,
+                words: &'w mut [WORD],
+            ) -> Result<&'w [WORD], Error> {
+                let len = words.len();
+                for (i, word) in words.iter_mut().enumerate() {
+                    nb::block!(self.send(*word))?;
+                    if i + 1 == len {
+                        self.send_crc();
+                    }
+                    *word = nb::block!(self.read())?;
+                }
+
+                // the received CRC ends up in the FIFO as well, the CRC error flag is set once
+                // it is complete
+                nb::block!(self.read())?;
+                self.check_errors()?;
+
+                Ok(words)
+            }
+
+            /// Checks the status register for errors and clears the overrun flag
+            fn check_errors(&mut self) -> Result<(), Error> {
+                let sr = self.spi.sr.read();
+
+                if sr.ovr().is_overrun() {
+                    // OVR is cleared by reading DR followed by SR, the received data is lost
+                    // NOTE(unsafe) see the `FullDuplex` implementation
+                    unsafe {
+                        ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
+                    }
+                    self.spi.sr.read();
+                    Err(Error::Overrun)
+                } else if sr.modf().is_fault() {
+                    Err(Error::ModeFault)
+                } else if sr.crcerr().is_no_match() {
+                    self.spi.sr.modify(|_, w| w.crcerr().clear_bit());
+                    Err(Error::Crc)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        impl<PINS, WORD: Word> FullDuplex<WORD> for $SPI<$SPIX, PINS, WORD> {
+            type Error = Error;
+
+            fn read(&mut self) -> nb::Result<WORD, Error> {
+                self.check_errors()?;
+
+                if self.spi.sr.read().rxne().is_not_empty() {
+                    // NOTE(read_volatile) read a byte or a half-word, which pops one frame
+                    // from the FIFO (the svd2rust API only allows reading a half-word)
+                    Ok(unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const WORD) })
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            fn send(&mut self, word: WORD) -> nb::Result<(), Error> {
+                self.check_errors()?;
+
+                if self.spi.sr.read().txe().is_empty() {
+                    // NOTE(write_volatile) see note above
+                    unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut WORD, word) }
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+        }
+
+        impl<PINS, WORD> $SPI<$SPIX, PINS, WORD> {
+            /// Waits until all frames are sent, then discards the received ones
+            ///
+            /// This also clears the overrun flag, which is expected when only writing.
+            fn flush_rx(&mut self) {
+                while self.spi.sr.read().ftlvl().bits() != 0 {}
+                while self.spi.sr.read().bsy().bit_is_set() {}
+
+                while self.spi.sr.read().frlvl().bits() != 0 {
+                    // NOTE(read_volatile) see the `FullDuplex` implementation
+                    unsafe {
+                        ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
+                    }
+                }
+
+                // OVR is cleared by reading DR followed by SR
+                self.spi.sr.read();
+            }
+        }
+
+        impl<PINS, WORD: Word> crate::hal::blocking::spi::transfer::Default<WORD>
+            for $SPI<$SPIX, PINS, WORD>
+        {
+        }
+
+        impl<PINS, WORD: Word> crate::hal::blocking::spi::Write<WORD> for $SPI<$SPIX, PINS, WORD> {
+            type Error = Error;
+
+            fn write(&mut self, words: &[WORD]) -> Result<(), Error> {
+                for word in words {
+                    loop {
+                        let sr = self.spi.sr.read();
+
+                        // overruns are ignored, as the received data is discarded anyway
+                        if sr.modf().is_fault() {
+                            return Err(Error::ModeFault);
+                        } else if sr.crcerr().is_no_match() {
+                            return Err(Error::Crc);
+                        } else if sr.txe().is_empty() {
+                            // NOTE(write_volatile) see the `FullDuplex` implementation
+                            unsafe {
+                                ptr::write_volatile(&self.spi.dr as *const _ as *mut WORD, *word)
+                            }
+                            break;
+                        }
+                    }
+                }
+
+                self.flush_rx();
+
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Asserts that `WORD` matches the configured frame size
+fn check_word_size<WORD: Word>(config: &Config) {
+    let matches = match WORD::size() {
+        dma::WordSize::Bits8 => config.frame_size <= 8,
+        dma::WordSize::Bits16 => config.frame_size > 8,
+        _ => false,
+    };
+    assert!(matches, "SPI word type doesn't match the frame size");
+}
+
+/// Writes DS and FRXTH of CR2 for the configured frame size
+macro_rules! frame_size {
+    ($w:ident, $config:ident) => {{
+        if $config.frame_size > 8 {
+            $w.frxth().half();
+        } else {
+            $w.frxth().quarter();
+        }
+
+        // NOTE(unsafe) the frame size is checked to be in 4..=16
+        unsafe { $w.ds().bits($config.frame_size - 1) }
+    }};
+}
+
+/// Writes the CR1 bits shared by master and slave mode
+///
+/// CPHA: phase
+/// CPOL: polarity
+/// LSBFIRST: bit order
+/// CRCEN: hardware CRC calculation
+/// CRCL: CRC length
+/// BIDIMODE, BIDIOE, RXONLY: usage of the data lines, starting out in `$direction` for half
+///                            duplex communication
+macro_rules! common_cr1 {
+    ($w:ident, $config:ident, $direction:expr) => {{
+        match $config.mode.phase {
+            Phase::CaptureOnFirstTransition => $w.cpha().first_edge(),
+            Phase::CaptureOnSecondTransition => $w.cpha().second_edge(),
+        };
+
+        match $config.mode.polarity {
+            Polarity::IdleLow => $w.cpol().idle_low(),
+            Polarity::IdleHigh => $w.cpol().idle_high(),
+        };
+
+        match $config.bit_order {
+            BitOrder::MsbFirst => $w.lsbfirst().msbfirst(),
+            BitOrder::LsbFirst => $w.lsbfirst().lsbfirst(),
+        };
+
+        match $config.communication {
+            Communication::FullDuplex => $w.bidimode().unidirectional().rxonly().clear_bit(),
+            Communication::HalfDuplex => $w
+                .bidimode()
+                .bit(true)
+                .bidioe()
+                .bit($direction == Direction::Transmit),
+            Communication::ReceiveOnly => $w.bidimode().unidirectional().rxonly().set_bit(),
+        };
+
+        $w.crcen()
+            .bit($config.crc_polynomial.is_some())
+            .crcl()
+            .bit($config.frame_size > 8)
+    }};
 }
 
 macro_rules! hal {
     ($($SPIX:ident: ($spiX:ident, $APBX:ident, $spiXen:ident, $spiXrst:ident, $pclkX:ident),)+) => {
         $(
-            impl<SCK, MISO, MOSI> Spi<$SPIX, (SCK, MISO, MOSI)> {
+            impl<SCK, MISO, MOSI, WORD: Word> Spi<$SPIX, (SCK, MISO, MOSI), WORD> {
                 /// Configures the SPI peripheral to operate in full duplex master mode
-                pub fn $spiX<F>(
+                ///
+                /// `config` is either a complete `Config` or just a `Mode`, which results
+                /// in 8-bit frames sent MSB first.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `WORD` doesn't match the frame size.
+                pub fn $spiX<C, F>(
                     spi: $SPIX,
                     pins: (SCK, MISO, MOSI),
-                    mode: Mode,
+                    config: C,
                     freq: F,
                     clocks: Clocks,
                     apb2: &mut $APBX,
                 ) -> Self
                 where
+                    C: Into<Config>,
                     F: Into<Hertz>,
                     SCK: SckPin<$SPIX>,
                     MISO: MisoPin<$SPIX>,
@@ -138,35 +670,29 @@ macro_rules! hal {
                     apb2.rstr().modify(|_, w| w.$spiXrst().reset());
                     apb2.rstr().modify(|_, w| w.$spiXrst().clear_bit());
 
+                    let config = config.into();
+                    check_word_size::<WORD>(&config);
+
                     // FRXTH: RXNE event is generated if the FIFO level is greater than or equal to
-                    //        8-bit
-                    // DS: 8-bit data size
+                    //        the size of one frame (8-bit or 16-bit)
+                    // DS: data size
                     // SSOE: Slave Select output disabled
-                    spi.cr2.write(|w| w.frxth().quarter().ds().eight_bit().ssoe().disabled());
+                    spi.cr2.write(|w| frame_size!(w, config).ssoe().disabled());
+
+                    if let Some(polynomial) = config.crc_polynomial {
+                        // NOTE(unsafe) any polynomial is allowed
+                        spi.crcpr.write(|w| unsafe { w.bits(u32::from(polynomial)) });
+                    }
 
-                    // CPHA: phase
-                    // CPOL: polarity
                     // MSTR: master mode
                     // BR: 1 MHz
-                    // SPE: SPI disabled
-                    // LSBFIRST: MSB first
                     // SSM: enable software slave management (NSS pin free for other uses)
                     // SSI: set nss high = master mode
-                    // CRCEN: hardware CRC calculation disabled
-                    // BIDIMODE: 2 line unidirectional (full duplex)
+                    // SPE: SPI disabled, the configuration (e.g. CRCEN) must not change while
+                    //      the SPI is enabled
                     spi.cr1.write(|w| {
                         w.mstr().master();
 
-                        match mode.phase {
-                            Phase::CaptureOnFirstTransition => w.cpha().first_edge(),
-                            Phase::CaptureOnSecondTransition => w.cpha().second_edge(),
-                        };
-
-                        match mode.polarity {
-                            Polarity::IdleLow => w.cpol().idle_low(),
-                            Polarity::IdleHigh => w.cpol().idle_high(),
-                        };
-
                         match clocks.$pclkX().0 / freq.into().0 {
                             0 => unreachable!(),
                             1..=2 => w.br().div2(),
@@ -179,21 +705,26 @@ macro_rules! hal {
                             _ => w.br().div256(),
                         };
 
-                        w.spe()
-                            .enabled()
-                            .lsbfirst()
-                            .lsbfirst()
+                        common_cr1!(w, config, Direction::Transmit)
                             .ssi()
                             .slave_not_selected()
                             .ssm()
                             .enabled()
-                            .crcen()
+                            .spe()
                             .disabled()
-                            .bidimode()
-                            .unidirectional()
                     });
 
-                    Spi { spi, pins }
+                    // SPE: SPI enabled, except for receive only communication as the clock
+                    //      would start right away
+                    if config.communication != Communication::ReceiveOnly {
+                        spi.cr1.modify(|_, w| w.spe().enabled());
+                    }
+
+                    Spi {
+                        spi,
+                        pins,
+                        _word: PhantomData,
+                    }
                 }
 
                 /// Releases the SPI peripheral and associated pins
@@ -202,51 +733,252 @@ macro_rules! hal {
                 }
             }
 
-            impl<PINS> FullDuplex<u8> for Spi<$SPIX, PINS> {
-                type Error = Error;
-
-                fn read(&mut self) -> nb::Result<u8, Error> {
-                    let sr = self.spi.sr.read();
-
-                    Err(if sr.ovr().is_overrun() {
-                        nb::Error::Other(Error::Overrun)
-                    } else if sr.modf().is_fault() {
-                        nb::Error::Other(Error::ModeFault)
-                    } else if sr.crcerr().is_no_match() {
-                        nb::Error::Other(Error::Crc)
-                    } else if sr.rxne().is_not_empty() {
-                        // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
-                        // reading a half-word)
-                        return Ok(unsafe {
-                            ptr::read_volatile(&self.spi.dr as *const _ as *const u8)
-                        });
-                    } else {
-                        nb::Error::WouldBlock
-                    })
+            impl<SCK, MISO, MOSI, NSS, WORD: Word> SpiSlave<$SPIX, (SCK, MISO, MOSI, NSS), WORD> {
+                /// Configures the SPI peripheral to operate in full duplex slave mode
+                ///
+                /// With `SoftwareNss` in place of a NSS pin, the slave starts out deselected.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `WORD` doesn't match the frame size.
+                pub fn $spiX<C>(
+                    spi: $SPIX,
+                    pins: (SCK, MISO, MOSI, NSS),
+                    config: C,
+                    apb2: &mut $APBX,
+                ) -> Self
+                where
+                    C: Into<Config>,
+                    SCK: SckPin<$SPIX>,
+                    MISO: MisoPin<$SPIX>,
+                    MOSI: MosiPin<$SPIX>,
+                    NSS: NssPin<$SPIX>,
+                {
+                    // enable or reset $SPIX
+                    apb2.enr().modify(|_, w| w.$spiXen().enabled());
+                    apb2.rstr().modify(|_, w| w.$spiXrst().reset());
+                    apb2.rstr().modify(|_, w| w.$spiXrst().clear_bit());
+
+                    let config = config.into();
+                    check_word_size::<WORD>(&config);
+
+                    // FRXTH: RXNE event is generated if the FIFO level is greater than or equal to
+                    //        the size of one frame (8-bit or 16-bit)
+                    // DS: data size
+                    // SSOE: Slave Select output disabled
+                    spi.cr2.write(|w| frame_size!(w, config).ssoe().disabled());
+
+                    if let Some(polynomial) = config.crc_polynomial {
+                        // NOTE(unsafe) any polynomial is allowed
+                        spi.crcpr.write(|w| unsafe { w.bits(u32::from(polynomial)) });
+                    }
+
+                    // MSTR: slave mode
+                    // SSM: software slave management, if no NSS pin is used
+                    // SSI: slave not selected (only relevant with software slave management)
+                    // SPE: SPI disabled, the configuration (e.g. CRCEN) must not change while
+                    //      the SPI is enabled
+                    spi.cr1.write(|w| {
+                        w.mstr().slave();
+
+                        common_cr1!(w, config, Direction::Receive)
+                            .ssm()
+                            .bit(NSS::SOFTWARE)
+                            .ssi()
+                            .slave_not_selected()
+                            .spe()
+                            .disabled()
+                    });
+
+                    // SPE: SPI enabled, so that the transmit FIFO can be preloaded
+                    spi.cr1.modify(|_, w| w.spe().enabled());
+
+                    SpiSlave {
+                        spi,
+                        pins,
+                        _word: PhantomData,
+                    }
                 }
 
-                fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
-                    let sr = self.spi.sr.read();
-
-                    Err(if sr.ovr().is_overrun() {
-                        nb::Error::Other(Error::Overrun)
-                    } else if sr.modf().is_fault() {
-                        nb::Error::Other(Error::ModeFault)
-                    } else if sr.crcerr().is_no_match() {
-                        nb::Error::Other(Error::Crc)
-                    } else if sr.txe().is_empty() {
-                        // NOTE(write_volatile) see note above
-                        unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut u8, byte) }
-                        return Ok(());
+                /// Releases the SPI peripheral and associated pins
+                pub fn free(self) -> ($SPIX, (SCK, MISO, MOSI, NSS)) {
+                    self.spi.cr1.modify(|_, w| w.spe().disabled());
+                    (self.spi, self.pins)
+                }
+            }
+
+            impl<SCK, MISO, MOSI, WORD> SpiSlave<$SPIX, (SCK, MISO, MOSI, SoftwareNss), WORD> {
+                /// Selects or deselects the slave
+                pub fn select(&mut self, selected: bool) {
+                    if selected {
+                        self.spi.cr1.modify(|_, w| w.ssi().slave_selected());
                     } else {
-                        nb::Error::WouldBlock
-                    })
+                        self.spi.cr1.modify(|_, w| w.ssi().slave_not_selected());
+                    }
                 }
             }
 
-            impl<PINS> crate::hal::blocking::spi::transfer::Default<u8> for Spi<$SPIX, PINS> {}
+            impl<PINS, WORD: Word> SpiSlave<$SPIX, PINS, WORD> {
+                /// Preloads the transmit FIFO with `words` ahead of a transfer
+                ///
+                /// Returns the number of words, which fit into the FIFO.
+                pub fn preload(&mut self, words: &[WORD]) -> usize {
+                    let mut loaded = 0;
+                    for word in words {
+                        if self.spi.sr.read().txe().is_not_empty() {
+                            break;
+                        }
+
+                        // NOTE(write_volatile) see the `FullDuplex` implementation
+                        unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut WORD, *word) }
+                        loaded += 1;
+                    }
+                    loaded
+                }
+
+                /// Returns `true` while a frame is being exchanged with the master
+                pub fn is_busy(&self) -> bool {
+                    self.spi.sr.read().bsy().bit_is_set()
+                }
+            }
 
-            impl<PINS> crate::hal::blocking::spi::write::Default<u8> for Spi<$SPIX, PINS> {}
+            impl<PINS, WORD: Word> Spi<$SPIX, PINS, WORD> {
+                /// Writes `buffer` using the DMA, the received frames are discarded
+                pub fn write_dma<TX>(
+                    self,
+                    buffer: &'static [WORD],
+                    mut channel: TX,
+                ) -> dma::Transfer<&'static [WORD], TX, Self>
+                where
+                    TX: dma::TxChannel<$SPIX>,
+                {
+                    // NOTE(unsafe) DR is accessed with the size of `WORD`, which matches the
+                    // frame size
+                    unsafe {
+                        channel.set_peripheral_address(
+                            &self.spi.dr as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+
+                    dma::Transfer::start_write(buffer, channel, self)
+                }
+
+                /// Reads into `buffer` using the DMA, while sending zeros
+                pub fn read_dma<RX, TX>(
+                    self,
+                    buffer: &'static mut [WORD],
+                    rx_channel: RX,
+                    mut tx_channel: TX,
+                ) -> dma::Transfer<&'static mut [WORD], RX, TxDma<$SPIX, PINS, WORD, TX>>
+                where
+                    RX: dma::RxChannel<$SPIX>,
+                    TX: dma::TxChannel<$SPIX>,
+                {
+                    static ZERO: u16 = 0;
+
+                    // NOTE(unsafe) the dummy word is 'static and never written
+                    unsafe {
+                        tx_channel.set_memory_address(
+                            &ZERO as *const _ as u32,
+                            dma::Increment::Disable,
+                        );
+                    }
+
+                    self.start_full_duplex_dma(buffer, rx_channel, tx_channel)
+                }
+
+                /// Sends `buffer` and replaces its content with the received frames using
+                /// the DMA
+                pub fn transfer_dma<RX, TX>(
+                    self,
+                    buffer: &'static mut [WORD],
+                    rx_channel: RX,
+                    mut tx_channel: TX,
+                ) -> dma::Transfer<&'static mut [WORD], RX, TxDma<$SPIX, PINS, WORD, TX>>
+                where
+                    RX: dma::RxChannel<$SPIX>,
+                    TX: dma::TxChannel<$SPIX>,
+                {
+                    // NOTE(unsafe) the transmit channel always runs ahead of the receive
+                    // channel, so every word is sent before it is overwritten
+                    unsafe {
+                        tx_channel.set_memory_address(
+                            buffer.as_ptr() as u32,
+                            dma::Increment::Enable,
+                        );
+                    }
+
+                    self.start_full_duplex_dma(buffer, rx_channel, tx_channel)
+                }
+
+                fn start_full_duplex_dma<RX, TX>(
+                    self,
+                    buffer: &'static mut [WORD],
+                    mut rx_channel: RX,
+                    mut tx_channel: TX,
+                ) -> dma::Transfer<&'static mut [WORD], RX, TxDma<$SPIX, PINS, WORD, TX>>
+                where
+                    RX: dma::RxChannel<$SPIX>,
+                    TX: dma::TxChannel<$SPIX>,
+                {
+                    let dr = &self.spi.dr as *const _ as u32;
+
+                    // NOTE(unsafe) DR is accessed with the size of `WORD`, which matches the
+                    // frame size
+                    unsafe {
+                        rx_channel.set_peripheral_address(dr, dma::Increment::Disable);
+                        tx_channel.set_peripheral_address(dr, dma::Increment::Disable);
+                    }
+                    tx_channel.set_transfer_length(buffer.len() as u16);
+                    tx_channel.set_word_size(WORD::size());
+                    tx_channel.set_direction(dma::Direction::FromMemory);
+
+                    let target = TxDma {
+                        spi: self,
+                        channel: tx_channel,
+                    };
+
+                    dma::Transfer::start_read(buffer, rx_channel, target)
+                }
+            }
+
+            impl<PINS, WORD> dma::Target for Spi<$SPIX, PINS, WORD> {
+                type Error = Void;
+
+                fn enable_dma(&mut self) {
+                    self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.spi.cr2.modify(|_, w| w.txdmaen().clear_bit());
+                    self.flush_rx();
+                }
+            }
+
+            impl<PINS, WORD, TX: dma::Channel> dma::Target for TxDma<$SPIX, PINS, WORD, TX> {
+                type Error = Void;
+
+                fn enable_dma(&mut self) {
+                    // the receive requests have to be enabled before the transmit ones, so no
+                    // received frame is missed
+                    self.spi.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
+                    self.channel.clear_event(dma::Event::Any);
+                    self.channel.enable();
+                    self.spi.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+                }
+
+                fn disable_dma(&mut self) {
+                    self.channel.disable();
+                    self.spi
+                        .spi
+                        .cr2
+                        .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+                }
+            }
+
+            full_duplex!(Spi, $SPIX);
+            full_duplex!(SpiSlave, $SPIX);
         )+
     }
 }
@@ -278,43 +1010,37 @@ hal! {
     SPI3: (spi3, APB1, spi3en, spi3rst, pclk1),
 }
 
-// FIXME not working
-// TODO measure if this actually faster than the default implementation
-// impl ::hal::blocking::spi::Write<u8> for Spi {
-//     type Error = Error;
-
-//     fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
-//         for byte in bytes {
-//             'l: loop {
-//                 let sr = self.spi.sr.read();
-
-//                 // ignore overruns because we don't care about the incoming data
-//                 // if sr.ovr().bit_is_set() {
-//                 // Err(nb::Error::Other(Error::Overrun))
-//                 // } else
-//                 if sr.modf().bit_is_set() {
-//                     return Err(Error::ModeFault);
-//                 } else if sr.crcerr().bit_is_set() {
-//                     return Err(Error::Crc);
-//                 } else if sr.txe().bit_is_set() {
-//                     // NOTE(write_volatile) see note above
-//                     unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut u8, *byte) }
-//                     break 'l;
-//                 } else {
-//                     // try again
-//                 }
-//             }
-//         }
-
-//         // wait until the transmission of the last byte is done
-//         while self.spi.sr.read().bsy().bit_is_set() {}
-
-//         // clear OVR flag
-//         unsafe {
-//             ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
-//         }
-//         self.spi.sr.read();
-
-//         Ok(())
-//     }
-// }
+#[cfg(not(feature = "stm32f301"))]
+unsafe impl dma::RxChannel<SPI1> for dma1::C2 {}
+#[cfg(not(feature = "stm32f301"))]
+unsafe impl dma::TxChannel<SPI1> for dma1::C3 {}
+
+#[cfg(not(feature = "stm32f334"))]
+unsafe impl dma::RxChannel<SPI2> for dma1::C4 {}
+#[cfg(not(feature = "stm32f334"))]
+unsafe impl dma::TxChannel<SPI2> for dma1::C5 {}
+
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398",
+))]
+unsafe impl dma::RxChannel<SPI3> for dma::dma2::C1 {}
+#[cfg(any(
+    feature = "stm32f302",
+    feature = "stm32f303xb",
+    feature = "stm32f303xc",
+    feature = "stm32f303xd",
+    feature = "stm32f303xe",
+    feature = "stm32f358",
+    feature = "stm32f373",
+    feature = "stm32f378",
+    feature = "stm32f398",
+))]
+unsafe impl dma::TxChannel<SPI3> for dma::dma2::C2 {}